@@ -0,0 +1,65 @@
+#![no_main]
+use egg::{Id, RecExpr};
+use egg_bench::lambda::Lambda;
+use libfuzzer_sys::arbitrary::{self, Arbitrary, Unstructured};
+use libfuzzer_sys::fuzz_target;
+
+// Includes the operator names on purpose, so that a variable called `let`
+// or `app` gets exercised. Symbols that look like a number or a boolean,
+// or that contain whitespace or parens, don't round-trip; see `Lambda`.
+const SYMBOLS: &[&str] = &[
+    "x", "y", "f", "fib", "var", "let", "app", "lam", "fix", "if", "+", "=",
+];
+
+const MAX_DEPTH: usize = 8;
+
+/// A tree-shaped (no sharing) `RecExpr`, laid out in the same post-order
+/// that the parser produces.
+#[derive(Debug)]
+struct ArbExpr(RecExpr<Lambda>);
+
+impl<'a> Arbitrary<'a> for ArbExpr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut expr = RecExpr::default();
+        gen(u, &mut expr, MAX_DEPTH)?;
+        Ok(ArbExpr(expr))
+    }
+}
+
+fn sym(u: &mut Unstructured, expr: &mut RecExpr<Lambda>) -> arbitrary::Result<Id> {
+    let s = *u.choose(SYMBOLS)?;
+    Ok(expr.add(Lambda::Symbol(s.into())))
+}
+
+fn gen(u: &mut Unstructured, expr: &mut RecExpr<Lambda>, depth: usize) -> arbitrary::Result<Id> {
+    let max_choice = if depth == 0 { 2 } else { 11 };
+    let child = |u: &mut Unstructured, expr: &mut RecExpr<Lambda>| gen(u, expr, depth - 1);
+    let node = match u.int_in_range(0..=max_choice)? {
+        0 => Lambda::Bool(u.arbitrary()?),
+        1 => Lambda::Num(u.arbitrary()?),
+        2 => return sym(u, expr),
+        3 => Lambda::Var(sym(u, expr)?),
+        4 => Lambda::Add([child(u, expr)?, child(u, expr)?]),
+        5 => Lambda::Eq([child(u, expr)?, child(u, expr)?]),
+        6 => Lambda::App([child(u, expr)?, child(u, expr)?]),
+        7 => Lambda::Lambda([sym(u, expr)?, child(u, expr)?]),
+        8 => Lambda::Let([sym(u, expr)?, child(u, expr)?, child(u, expr)?]),
+        9 => Lambda::Fix([sym(u, expr)?, child(u, expr)?]),
+        10 => Lambda::LetRec([sym(u, expr)?, child(u, expr)?, child(u, expr)?]),
+        _ => Lambda::If([child(u, expr)?, child(u, expr)?, child(u, expr)?]),
+    };
+    Ok(expr.add(node))
+}
+
+fuzz_target!(|expr: ArbExpr| {
+    let printed = expr.0.to_string();
+    let reparsed: RecExpr<Lambda> = printed
+        .parse()
+        .unwrap_or_else(|e| panic!("failed to reparse {}: {:?}", printed, e));
+    assert_eq!(
+        expr.0.as_ref(),
+        reparsed.as_ref(),
+        "round trip changed {}",
+        printed
+    );
+});
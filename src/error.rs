@@ -0,0 +1,95 @@
+use std::fmt;
+use std::io;
+
+/// What can go wrong running a bench, for library users who'd rather not
+/// panic. The command line still panics, with these as the messages.
+#[derive(Debug)]
+pub enum Error {
+    /// A term, pattern, or file that didn't parse.
+    Parse(String),
+    Io(io::Error),
+    /// A run stopped on a limit when it had to finish, with the stop reason.
+    LimitExceeded(String),
+    UnknownBench(String),
+    /// Rule names that aren't in the rule set they were looked up in.
+    UnknownRule(Vec<String>),
+    /// Names shared by more than one rule in a set. egg keys scheduler state
+    /// by rule name, so these silently misbehave.
+    DuplicateRules(Vec<String>),
+    NotSaturated {
+        bench: String,
+        stop_reason: String,
+    },
+    TooFewIterations {
+        bench: String,
+        iterations: usize,
+        min: usize,
+    },
+    /// Goals `check_equiv` couldn't prove equal to `start`, and why the run
+    /// stopped.
+    NotEquivalent {
+        start: String,
+        goals: Vec<String>,
+        stop_reason: String,
+    },
+    /// The ILP solver for `optimal_dag_extract` failed, with its error.
+    Solver(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(msg) => write!(f, "parse error: {}", msg),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::LimitExceeded(reason) => write!(f, "stopped early: {}", reason),
+            Error::UnknownBench(name) => write!(f, "unknown bench {}", name),
+            Error::UnknownRule(names) => write!(f, "unknown rules: {}", names.join(", ")),
+            Error::DuplicateRules(names) => {
+                write!(f, "duplicate rule names: {}", names.join(", "))
+            }
+            Error::NotSaturated { bench, stop_reason } => {
+                write!(
+                    f,
+                    "{} did not saturate: stopped with {}",
+                    bench, stop_reason
+                )
+            }
+            Error::TooFewIterations {
+                bench,
+                iterations,
+                min,
+            } => write!(
+                f,
+                "{} ran {} iterations, fewer than the minimum of {}",
+                bench, iterations, min
+            ),
+            Error::NotEquivalent {
+                start,
+                goals,
+                stop_reason,
+            } => write!(
+                f,
+                "{} isn't equivalent to {} (stopped with {})",
+                start,
+                goals.join(", "),
+                stop_reason
+            ),
+            Error::Solver(msg) => write!(f, "ILP extraction failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
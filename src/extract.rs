@@ -0,0 +1,667 @@
+use crate::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// How to extract the best term from the final e-graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostMetric {
+    /// egg's greedy `Extractor` with `AstSize`.
+    AstSize,
+    /// The smallest DAG, found by solving an ILP.
+    #[cfg(feature = "lp")]
+    OptimalDag,
+}
+
+impl Default for CostMetric {
+    fn default() -> Self {
+        CostMetric::AstSize
+    }
+}
+
+impl FromStr for CostMetric {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ast-size" => Ok(CostMetric::AstSize),
+            #[cfg(feature = "lp")]
+            "optimal-dag" => Ok(CostMetric::OptimalDag),
+            #[cfg(not(feature = "lp"))]
+            "optimal-dag" => Err("optimal-dag needs the lp feature".into()),
+            _ => Err(format!(
+                "cost metric should be ast-size or optimal-dag, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// How to extract the `AstSize` results of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractMode {
+    /// egg's `Extractor`, which keeps a cost and e-node for every e-class.
+    Full,
+    /// `streaming_extract`, keeping at most `max_working_set` candidates.
+    Streaming { max_working_set: usize },
+    /// egg's `Extractor` with `TieBreak`, so equally small terms come out
+    /// the same way every time.
+    TieBreak(Nesting),
+}
+
+/// Which way `TieBreak` prefers chains of a binary operator to lean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nesting {
+    /// `(+ (+ a b) c)`
+    Left,
+    /// `(+ a (+ b c))`
+    Right,
+}
+
+impl FromStr for Nesting {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Nesting::Left),
+            "right" => Ok(Nesting::Right),
+            _ => Err(format!("nesting should be left or right, got {}", s)),
+        }
+    }
+}
+
+/// `AstSize`, with ties broken first toward `nesting` and then by a hash
+/// of the term's structure, smallest first. Which of several equally small terms
+/// egg's `Extractor` picks otherwise depends on e-node order, which rule
+/// order and e-class ids can change, so snapshots of AC-rewritten terms
+/// flip between equivalent forms. Extraction is still greedy: an e-class'
+/// best term is chosen without knowing if its parent will see it on the
+/// wrong side, so the nesting is a preference rather than a guarantee. The
+/// hash is `DefaultHasher::new()`'s, which has fixed keys, so the term that
+/// wins a tie is the same from run to run, though which one it is isn't
+/// meaningful.
+#[derive(Debug, Clone, Copy)]
+pub struct TieBreak(pub Nesting);
+
+/// A `TieBreak` cost, ordered by its fields in order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TieBreakCost {
+    pub size: usize,
+    /// E-nodes of a binary operator with the same operator as their child
+    /// on the side `Nesting` doesn't want.
+    pub misnested: usize,
+    /// A hash of the term's operators and shape, rather than the term, so
+    /// comparing costs doesn't mean comparing whole terms.
+    pub hash: u64,
+    op: u64,
+}
+
+impl<L: Language + std::fmt::Display> CostFunction<L> for TieBreak {
+    type Cost = TieBreakCost;
+    fn cost<C>(&mut self, enode: &L, mut costs: C) -> TieBreakCost
+    where
+        C: FnMut(Id) -> TieBreakCost,
+    {
+        let mut hasher = DefaultHasher::new();
+        enode.to_string().hash(&mut hasher);
+        let op = hasher.finish();
+        let children: Vec<TieBreakCost> = enode.children().iter().map(|&c| costs(c)).collect();
+        let wrong_side = match (self.0, children.as_slice()) {
+            (Nesting::Left, [_, right]) => right.op == op,
+            (Nesting::Right, [left, _]) => left.op == op,
+            _ => false,
+        };
+        let mut hasher = DefaultHasher::new();
+        op.hash(&mut hasher);
+        for c in &children {
+            c.hash.hash(&mut hasher);
+        }
+        TieBreakCost {
+            size: children
+                .iter()
+                .fold(1, |size: usize, c| size.saturating_add(c.size)),
+            misnested: children.iter().map(|c| c.misnested).sum::<usize>() + wrong_side as usize,
+            hash: hasher.finish(),
+            op,
+        }
+    }
+}
+
+impl Default for ExtractMode {
+    fn default() -> Self {
+        ExtractMode::Full
+    }
+}
+
+/// What `streaming_extract` found.
+#[derive(Debug, Clone)]
+pub struct StreamingExtraction<L> {
+    pub cost: usize,
+    pub expr: RecExpr<L>,
+    /// The most candidate e-nodes queued at once.
+    pub peak_working_set: usize,
+}
+
+/// Extracts the smallest term of `root` bottom up, best first, from only
+/// the e-classes reachable from it. Candidate e-nodes, whose children all
+/// have a best term, wait in a queue by cost; the cheapest one is always
+/// its e-class' best, and finishing an e-class may ready its parents.
+/// Extraction stops once `root` is finished.
+///
+/// The queue holds at most `max_working_set` candidates, dropping the most
+/// expensive when it's over. A dropped e-node is never queued again, so the
+/// result can be worse than `Extractor`'s, or missing (`None`) if every
+/// e-node of some e-class on the way was dropped. Under the bound this
+/// finds the same cost as `Extractor`. Only the queue is bounded: the index
+/// of which e-nodes wait on which e-classes, and the best e-node (by index,
+/// not a copy) of each finished e-class, grow with the e-classes reachable
+/// from `root`, so this caps the candidates rather than the memory used.
+pub fn streaming_extract<L: Language, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    root: Id,
+    max_working_set: usize,
+) -> Option<StreamingExtraction<L>> {
+    assert!(
+        max_working_set > 0,
+        "streaming extraction needs a working set"
+    );
+    let root = egraph.find(root);
+    let mut queue: BTreeSet<(usize, Id, usize)> = BTreeSet::new();
+    let mut peak_working_set = 0;
+    let mut push = |queue: &mut BTreeSet<_>, candidate| {
+        queue.insert(candidate);
+        if queue.len() > max_working_set {
+            let worst = *queue.iter().next_back().unwrap();
+            queue.remove(&worst);
+        }
+        peak_working_set = peak_working_set.max(queue.len());
+    };
+
+    // e-node (e-class, index) pairs by the children they're waiting on, and
+    // how many distinct children each is still waiting on
+    let mut waiting: HashMap<Id, Vec<(Id, usize)>> = HashMap::new();
+    let mut unfinished: HashMap<(Id, usize), usize> = HashMap::new();
+    for id in reachable_classes(egraph, &[root]) {
+        for (i, node) in egraph[id].nodes.iter().enumerate() {
+            let mut children: Vec<Id> = node.children().iter().map(|&c| egraph.find(c)).collect();
+            children.sort();
+            children.dedup();
+            if children.is_empty() {
+                push(&mut queue, (1, id, i));
+            } else {
+                for &child in &children {
+                    waiting.entry(child).or_default().push((id, i));
+                }
+                unfinished.insert((id, i), children.len());
+            }
+        }
+    }
+
+    let mut best: HashMap<Id, (usize, usize)> = HashMap::new();
+    while let Some(&candidate) = queue.iter().next() {
+        queue.remove(&candidate);
+        let (cost, id, i) = candidate;
+        if best.contains_key(&id) {
+            continue;
+        }
+        best.insert(id, (cost, i));
+        if id == root {
+            break;
+        }
+        for &(parent, j) in waiting.get(&id).into_iter().flatten() {
+            let left = unfinished.get_mut(&(parent, j)).unwrap();
+            *left -= 1;
+            if *left == 0 && !best.contains_key(&parent) {
+                let cost = egraph[parent].nodes[j]
+                    .children()
+                    .iter()
+                    .fold(1, |cost: usize, &c| {
+                        cost.saturating_add(best[&egraph.find(c)].0)
+                    });
+                push(&mut queue, (cost, parent, j));
+            }
+        }
+    }
+
+    let cost = best.get(&root)?.0;
+    // every finished e-class' children finished before it, so this can't
+    // get stuck
+    let mut expr = RecExpr::default();
+    let mut ids: HashMap<Id, Id> = HashMap::new();
+    let mut todo = vec![root];
+    while let Some(&id) = todo.last() {
+        if ids.contains_key(&id) {
+            todo.pop();
+            continue;
+        }
+        let node = &egraph[id].nodes[best[&id].1];
+        let missing: Vec<Id> = node
+            .children()
+            .iter()
+            .map(|&c| egraph.find(c))
+            .filter(|c| !ids.contains_key(c))
+            .collect();
+        if missing.is_empty() {
+            let node = node.clone().map_children(|c| ids[&egraph.find(c)]);
+            ids.insert(id, expr.add(node));
+            todo.pop();
+        } else {
+            todo.extend(missing);
+        }
+    }
+    Some(StreamingExtraction {
+        cost,
+        expr,
+        peak_working_set,
+    })
+}
+
+/// Checks `streaming_extract` with `max_working_set` gets the same term for
+/// `root` as `Extractor` with `AstSize`, and kept to its bound. Returns the
+/// peak working set. Ties between equally small terms can be broken either
+/// way, so this is only meaningful where the smallest term is unique.
+pub fn check_streaming_extraction<L: Language + std::fmt::Display, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    root: Id,
+    max_working_set: usize,
+) -> Result<usize, String> {
+    let (cost, expected) = Extractor::new(egraph, AstSize).find_best(root);
+    let streamed = streaming_extract(egraph, root, max_working_set)
+        .ok_or_else(|| format!("streaming extraction of {} found nothing", root))?;
+    if streamed.peak_working_set > max_working_set {
+        return Err(format!(
+            "working set reached {}, over the bound of {}",
+            streamed.peak_working_set, max_working_set
+        ));
+    }
+    if streamed.cost != cost || streamed.expr.pretty(80) != expected.pretty(80) {
+        return Err(format!(
+            "streaming extraction found {} (cost {}), but Extractor found {} (cost {})",
+            streamed.expr.pretty(80),
+            streamed.cost,
+            expected.pretty(80),
+            cost
+        ));
+    }
+    Ok(streamed.peak_working_set)
+}
+
+/// A hash of the canonical structure of `egraph`: every e-class id with its
+/// sorted e-nodes, their children made canonical. Equal e-graphs hash
+/// equally, and any change to the e-graph almost certainly changes the hash.
+pub fn egraph_hash<L: Language, A: Analysis<L>>(egraph: &EGraph<L, A>) -> u64 {
+    let mut classes: Vec<_> = egraph.classes().collect();
+    classes.sort_by_key(|class| class.id);
+    let mut hasher = DefaultHasher::new();
+    for class in classes {
+        class.id.hash(&mut hasher);
+        let mut nodes: Vec<L> = class
+            .nodes
+            .iter()
+            .map(|node| node.clone().map_children(|c| egraph.find(c)))
+            .collect();
+        nodes.sort();
+        nodes.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Caches `AstSize` extractions of an e-graph by root, so re-extracting an
+/// unchanged e-graph (repeats, suites) doesn't pay for a new `Extractor`.
+/// The cache remembers the `egraph_hash` it was filled for, and empties
+/// itself when it's asked about an e-graph with another hash.
+pub struct ExtractionCache<L: Language> {
+    hash: Option<u64>,
+    entries: HashMap<Id, RecExpr<L>>,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl<L: Language> Default for ExtractionCache<L> {
+    fn default() -> Self {
+        Self {
+            hash: None,
+            entries: Default::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl<L: Language> ExtractionCache<L> {
+    /// The best `AstSize` term for `root`, extracted only if this e-graph,
+    /// as it is now, hasn't had `root` extracted before.
+    pub fn extract<A: Analysis<L>>(&mut self, egraph: &EGraph<L, A>, root: Id) -> RecExpr<L> {
+        let hash = egraph_hash(egraph);
+        if self.hash != Some(hash) {
+            self.entries.clear();
+            self.hash = Some(hash);
+        }
+        let root = egraph.find(root);
+        if let Some(expr) = self.entries.get(&root) {
+            self.hits += 1;
+            return expr.clone();
+        }
+        self.misses += 1;
+        let (_, expr) = Extractor::new(egraph, AstSize).find_best(root);
+        self.entries.insert(root, expr.clone());
+        expr
+    }
+}
+
+/// The number of distinct e-classes in the greedy `AstSize` extraction of
+/// `root`, that is, its size as a DAG rather than as a tree.
+pub fn greedy_dag_size<L: Language, A: Analysis<L>>(egraph: &EGraph<L, A>, root: Id) -> usize {
+    let extractor = Extractor::new(egraph, AstSize);
+    let mut seen = HashSet::new();
+    let mut todo = vec![egraph.find(root)];
+    while let Some(id) = todo.pop() {
+        if seen.insert(id) {
+            let node = extractor.find_best_node(id);
+            todo.extend(node.children().iter().map(|&c| egraph.find(c)));
+        }
+    }
+    seen.len()
+}
+
+/// The canonical ids of the e-classes reachable from `roots`.
+pub fn reachable_classes<L: Language, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    roots: &[Id],
+) -> HashSet<Id> {
+    let mut seen = HashSet::new();
+    let mut todo: Vec<Id> = roots.iter().map(|&root| egraph.find(root)).collect();
+    while let Some(id) = todo.pop() {
+        if seen.insert(id) {
+            for node in &egraph[id].nodes {
+                todo.extend(node.children().iter().map(|&c| egraph.find(c)));
+            }
+        }
+    }
+    seen
+}
+
+/// The canonical ids of the e-classes that contain themselves, through some
+/// chain of e-nodes' children, in id order. `fix` makes these: its unrolling
+/// `(let v (fix v e) e)` ends up in the e-class of its own child `(fix v e)`.
+pub fn cyclic_eclasses<L: Language, A: Analysis<L>>(egraph: &EGraph<L, A>) -> Vec<Id> {
+    let children = |id: Id| -> Vec<Id> {
+        let mut children: Vec<Id> = egraph[id]
+            .nodes
+            .iter()
+            .flat_map(|node| node.children().iter().map(|&c| egraph.find(c)))
+            .collect();
+        children.sort();
+        children.dedup();
+        children
+    };
+    let mut ids: Vec<Id> = egraph.classes().map(|class| class.id).collect();
+    ids.sort();
+    let graph: HashMap<Id, Vec<Id>> = ids.iter().map(|&id| (id, children(id))).collect();
+    let mut parents: HashMap<Id, Vec<Id>> = HashMap::new();
+    for (&id, children) in &graph {
+        for &child in children {
+            parents.entry(child).or_default().push(id);
+        }
+    }
+
+    // Kosaraju's strongly connected components: order the e-classes by when
+    // a depth first search finishes them, then search the reversed graph in
+    // reverse of that order
+    let mut order = vec![];
+    let mut seen = HashSet::new();
+    for &start in &ids {
+        if !seen.insert(start) {
+            continue;
+        }
+        let mut stack = vec![(start, 0)];
+        while let Some((id, next)) = stack.pop() {
+            match graph[&id].get(next) {
+                Some(&child) => {
+                    stack.push((id, next + 1));
+                    if seen.insert(child) {
+                        stack.push((child, 0));
+                    }
+                }
+                None => order.push(id),
+            }
+        }
+    }
+    let mut cyclic = vec![];
+    let mut assigned = HashSet::new();
+    for &start in order.iter().rev() {
+        if !assigned.insert(start) {
+            continue;
+        }
+        let mut component = vec![start];
+        let mut todo = vec![start];
+        while let Some(id) = todo.pop() {
+            for &parent in parents.get(&id).into_iter().flatten() {
+                if assigned.insert(parent) {
+                    component.push(parent);
+                    todo.push(parent);
+                }
+            }
+        }
+        if component.len() > 1 || graph[&start].contains(&start) {
+            cyclic.extend(component);
+        }
+    }
+    cyclic.sort();
+    cyclic
+}
+
+pub fn has_cycles<L: Language, A: Analysis<L>>(egraph: &EGraph<L, A>) -> bool {
+    !cyclic_eclasses(egraph).is_empty()
+}
+
+/// The canonical ids of the e-classes without a finite term, where every
+/// e-node has a child on a cycle. Adding e-nodes one at a time can't make
+/// these, as each e-class keeps the e-node it was made with, but an e-graph
+/// built some other way could, and egg's `Extractor` can't extract them.
+pub fn unextractable_classes<L: Language, A: Analysis<L>>(egraph: &EGraph<L, A>) -> HashSet<Id> {
+    let mut extractable = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for class in egraph.classes() {
+            if extractable.contains(&class.id) {
+                continue;
+            }
+            let finite = class.nodes.iter().any(|node| {
+                node.children()
+                    .iter()
+                    .all(|&c| extractable.contains(&egraph.find(c)))
+            });
+            if finite {
+                extractable.insert(class.id);
+                changed = true;
+            }
+        }
+    }
+    egraph
+        .classes()
+        .map(|class| class.id)
+        .filter(|id| !extractable.contains(id))
+        .collect()
+}
+
+/// A copy of `egraph` with only the e-classes reachable from `roots`, so an
+/// `Extractor` doesn't have to visit the rest. Returns the copy, the roots'
+/// ids in it, and how many e-classes were left out. The copy is built by
+/// adding e-nodes, so the analysis runs again on everything reachable, and
+/// e-nodes with a child that has no finite term (see
+/// `unextractable_classes`) are left out, as no extraction can use them.
+/// The roots must have finite terms.
+pub fn prune_unreachable<L: Language, A: Analysis<L> + Clone>(
+    egraph: &EGraph<L, A>,
+    roots: &[Id],
+) -> (EGraph<L, A>, Vec<Id>, usize) {
+    let reachable = reachable_classes(egraph, roots);
+    let mut pruned = EGraph::new(egraph.analysis.clone());
+    let mut ids: HashMap<Id, Id> = HashMap::new();
+    let mut pending: Vec<(Id, &L)> = reachable
+        .iter()
+        .flat_map(|&id| egraph[id].nodes.iter().map(move |node| (id, node)))
+        .collect();
+    // an e-node can be added once its children have been, so this adds
+    // every e-node whose e-class has a finite term under it, and then stops
+    // making progress
+    while !pending.is_empty() {
+        let mut blocked = vec![];
+        for &(class, node) in &pending {
+            let ready = node
+                .children()
+                .iter()
+                .all(|&c| ids.contains_key(&egraph.find(c)));
+            if !ready {
+                blocked.push((class, node));
+                continue;
+            }
+            let node = node.clone().map_children(|c| ids[&egraph.find(c)]);
+            let id = pruned.add(node);
+            match ids.get(&class) {
+                Some(&existing) => {
+                    pruned.union(existing, id);
+                }
+                None => {
+                    ids.insert(class, id);
+                }
+            }
+        }
+        if blocked.len() == pending.len() {
+            break;
+        }
+        pending = blocked;
+    }
+    pruned.rebuild();
+    let roots = roots
+        .iter()
+        .map(|&root| pruned.find(ids[&egraph.find(root)]))
+        .collect();
+    let removed = egraph.number_of_classes() - ids.len();
+    (pruned, roots, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::{
+        lambda_bench1, lambda_bench_manyvars, program_bench, rules_for, Lambda, LambdaAnalysis,
+        RuleTag,
+    };
+
+    /// Streaming extraction of a large sum matches `Extractor`, with a working
+    /// set smaller than the e-graph. The variables are distinct, so there's
+    /// one smallest term, and there are no rules, so the e-graph is acyclic.
+    #[test]
+    fn streaming_matches_extractor() {
+        let bench = lambda_bench_manyvars(200).with_rules(vec![]);
+        let (runner, _) = bench.saturate(&RunConfig::default()).unwrap();
+        let max_working_set = 256;
+        assert!(runner.egraph.number_of_classes() > max_working_set);
+        check_streaming_extraction(&runner.egraph, runner.roots[0], max_working_set).unwrap();
+    }
+
+    /// Pruning `lambda1` leaves out the e-classes `if-elim`'s condition adds
+    /// off to the side, and extracts a term as small. Ties may break the
+    /// other way in the copy, so the term only has to be in the root's
+    /// e-class, not the same.
+    #[test]
+    fn prune_unreachable_keeps_results() {
+        let bench = lambda_bench1();
+        let (mut runner, full) = bench.saturate(&RunConfig::default()).unwrap();
+        let config = RunConfig {
+            prune_unreachable: true,
+            ..Default::default()
+        };
+        let pruned = bench.run(&config).unwrap();
+        assert!(pruned.pruned_classes > 0);
+        assert_eq!(pruned.best_cost, full.best_cost);
+        let root = runner.egraph.find(runner.roots[0]);
+        let result = runner.egraph.add_expr(&pruned.results[0].parse().unwrap());
+        assert_eq!(runner.egraph.find(result), root);
+    }
+
+    /// Extracting an AC-saturated sum of four variables with
+    /// `ExtractMode::TieBreak(Nesting::Right)` gets the same right-nested
+    /// sum whatever order the rules ran in.
+    #[test]
+    fn tie_break_across_rule_orders() {
+        let mut first: Option<Vec<String>> = None;
+        let bench = program_bench("ac-sum", "(+ (+ (var d) (var c)) (+ (var b) (var a)))")
+            .with_rules(rules_for(&[RuleTag::Arith]));
+        for seed in 0..4 {
+            let config = RunConfig {
+                extract_mode: ExtractMode::TieBreak(Nesting::Right),
+                shuffle_rules_seed: Some(seed),
+                assert_saturates: true,
+                ..Default::default()
+            };
+            let report = bench.run(&config).unwrap();
+            let result = &report.results[0];
+            assert!(!result.contains("(+ (+"), "{} isn't right-nested", result);
+            for v in &["a", "b", "c", "d"] {
+                assert!(result.contains(&format!("(var {})", v)), "{}", result);
+            }
+            match &first {
+                Some(first) => assert_eq!(&report.results, first, "rule seed {}", seed),
+                None => first = Some(report.results),
+            }
+        }
+    }
+
+    /// Unioning `x` with `(+ x 0)` puts the e-class of `x` on a cycle
+    /// through itself, and only that e-class.
+    #[test]
+    fn union_makes_cycle() {
+        let mut egraph: EGraph<Lambda, LambdaAnalysis> = EGraph::default();
+        let x = egraph.add_expr(&"(var x)".parse().unwrap());
+        let sum = egraph.add_expr(&"(+ (var x) 0)".parse().unwrap());
+        egraph.rebuild();
+        assert!(!has_cycles(&egraph));
+        egraph.union(x, sum);
+        egraph.rebuild();
+        assert!(has_cycles(&egraph));
+        assert_eq!(cyclic_eclasses(&egraph), vec![egraph.find(x)]);
+    }
+
+    /// Extracting an unchanged e-graph again is a cache hit, and changing
+    /// the e-graph empties the cache.
+    #[test]
+    fn extraction_cache_hits() {
+        let mut egraph: EGraph<Lambda, LambdaAnalysis> = EGraph::default();
+        let root = egraph.add_expr(&"(+ (var x) 1)".parse().unwrap());
+        egraph.rebuild();
+        let mut cache = ExtractionCache::default();
+        let first = cache.extract(&egraph, root);
+        assert_eq!((cache.hits, cache.misses), (0, 1));
+        assert_eq!(cache.extract(&egraph, root), first);
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+        egraph.add_expr(&"(var y)".parse().unwrap());
+        egraph.rebuild();
+        cache.extract(&egraph, root);
+        assert_eq!((cache.hits, cache.misses), (1, 2));
+    }
+
+    /// With working sets too small for the saturated `lambda1`, streaming
+    /// extraction keeps to its bound and either finds nothing or a term that
+    /// costs what it says, and no less than `Extractor`'s.
+    #[test]
+    fn streaming_over_bound() {
+        let (runner, _) = lambda_bench1().saturate(&RunConfig::default()).unwrap();
+        let root = runner.roots[0];
+        let (best, _) = Extractor::new(&runner.egraph, AstSize).find_best(root);
+        let nodes: usize = reachable_classes(&runner.egraph, &[root])
+            .into_iter()
+            .map(|id| runner.egraph[id].nodes.len())
+            .sum();
+        for &max_working_set in &[1, 2, 4, 16, 64] {
+            assert!(max_working_set < nodes);
+            if let Some(streamed) = streaming_extract(&runner.egraph, root, max_working_set) {
+                assert!(streamed.peak_working_set <= max_working_set);
+                assert!(streamed.cost >= best, "{} < {}", streamed.cost, best);
+                assert_eq!(AstSize.cost_rec(&streamed.expr), streamed.cost);
+            }
+        }
+    }
+}
@@ -1,60 +1,305 @@
 use crate::*;
 use egg::{define_language, rewrite as rw, Id};
+use serde::{Deserialize, Serialize};
 use std::cmp::*;
 use std::collections::*;
 
+mod church;
+pub use church::*;
+#[cfg(test)]
+mod confluence;
+mod dsl;
+pub use dsl::*;
+mod interp;
+pub use interp::*;
+mod random;
+pub use random::*;
+mod term;
+pub use term::*;
+
+const ZERO_ONE: &str = "(let zeroone (lam x
+        (if (= (var x) 0)
+            0
+            1))
+        (+ (app (var zeroone) 0)
+        (app (var zeroone) 10)))";
+
+const REPEAT: &str = "(let compose (lam f (lam g (lam x (app (var f)
+                                    (app (var g) (var x))))))
+    (let repeat (fix repeat (lam fun (lam n
+        (if (= (var n) 0)
+            (lam i (var i))
+            (app (app (var compose) (var fun))
+                (app (app (var repeat)
+                        (var fun))
+                    (+ (var n) -1)))))))
+    (let add1 (lam y (+ (var y) 1))
+    (app (app (var repeat)
+            (var add1))
+        2))))";
+
+const FIB: &str = "(let fib (fix fib (lam n
+        (if (= (var n) 0)
+            0
+        (if (= (var n) 1)
+            1
+        (+ (app (var fib)
+                (+ (var n) -1))
+            (app (var fib)
+                (+ (var n) -2)))))))
+        (app (var fib) 4))";
+
+/// `FIB` with `letrec` in place of `let` and `fix`.
+const FIB_LETREC: &str = "(letrec fib (lam n
+        (if (= (var n) 0)
+            0
+        (if (= (var n) 1)
+            1
+        (+ (app (var fib)
+                (+ (var n) -1))
+            (app (var fib)
+                (+ (var n) -2))))))
+        (app (var fib) 4))";
+
 pub fn lambda_bench() -> Bench<Lambda, LambdaAnalysis> {
     Bench {
         name: "lambda".into(),
+        analysis: Default::default(),
         rules: rules(),
-        bench_pats: parse_patterns("lambda"),
-        start_exprs: vec![
-            "(let zeroone (lam x
-                (if (= (var x) 0)
-                    0
-                    1))
-                (+ (app (var zeroone) 0)
-                (app (var zeroone) 10)))",
-            "(let compose (lam f (lam g (lam x (app (var f)
-                                            (app (var g) (var x))))))
-            (let repeat (fix repeat (lam fun (lam n
-                (if (= (var n) 0)
-                    (lam i (var i))
-                    (app (app (var compose) (var fun))
-                        (app (app (var repeat)
-                                (var fun))
-                            (+ (var n) -1)))))))
-            (let add1 (lam y (+ (var y) 1))
-            (app (app (var repeat)
-                    (var add1))
-                2))))",
-            "(let fib (fix fib (lam n
-                (if (= (var n) 0)
-                    0
-                (if (= (var n) 1)
-                    1
-                (+ (app (var fib)
-                        (+ (var n) -1))
-                    (app (var fib)
-                        (+ (var n) -2)))))))
-                (app (var fib) 4))",
-        ],
+        seed_equivalences: vec![],
+        union_seeds: true,
+        assumptions: vec![],
+        bench_pats: read_patterns("lambda")
+            .unwrap_or_else(|e| panic!("can't read patterns.csv: {}", e)),
+        compiled_pats: Default::default(),
+        pattern_compiles: Default::default(),
+        start_exprs: vec![ZERO_ONE.into(), REPEAT.into(), FIB.into()],
+        tags: vec!["arithmetic", "control", "recursion"],
+    }
+}
+
+pub(crate) fn program_bench(name: &str, start_expr: &str) -> Bench<Lambda, LambdaAnalysis> {
+    Bench {
+        name: name.into(),
+        start_exprs: vec![start_expr.into()],
+        ..lambda_bench()
+    }
+}
+
+/// The programs of `lambda_bench` on their own, one benchmark each.
+pub fn lambda_bench0() -> Bench<Lambda, LambdaAnalysis> {
+    program_bench("lambda0", ZERO_ONE).with_tags(&["arithmetic", "control"])
+}
+
+pub fn lambda_bench1() -> Bench<Lambda, LambdaAnalysis> {
+    program_bench("lambda1", REPEAT).with_tags(&["recursion", "higher-order"])
+}
+
+pub fn lambda_bench2() -> Bench<Lambda, LambdaAnalysis> {
+    program_bench("lambda2", FIB).with_tags(&["recursion", "arithmetic"])
+}
+
+/// `lambda_bench2` written with `letrec`, which should get to the same
+/// result after one more rewrite.
+pub fn lambda_bench_letrec() -> Bench<Lambda, LambdaAnalysis> {
+    program_bench("letrec", FIB_LETREC).with_tags(&["recursion", "arithmetic"])
+}
+
+/// A smoke test for the harness: `if-true` solves it in one iteration.
+pub fn lambda_bench_onestep() -> Bench<Lambda, LambdaAnalysis> {
+    program_bench("onestep", "(if true 1 2)").with_tags(&["control", "smoke"])
+}
+
+/// Checks a report of `lambda_bench_onestep` extracted `1` in one iteration
+/// that did something, plus the one that found nothing left to do.
+pub fn check_onestep(report: &BenchReport) -> Result<(), String> {
+    if report.results != ["1"] {
+        return Err(format!("extracted {:?}, not 1", report.results));
+    }
+    if !(1..=2).contains(&report.iterations) {
+        return Err(format!(
+            "took {} iterations, not 1 (or 2 counting the one that saturates)",
+            report.iterations
+        ));
+    }
+    Ok(())
+}
+
+/// A balanced sum of `terms`, so the parser doesn't recurse as deep as
+/// there are terms.
+fn balanced_sum(terms: &[String]) -> String {
+    match terms {
+        [] => "0".into(),
+        [term] => term.clone(),
+        _ => {
+            let (left, right) = terms.split_at(terms.len() / 2);
+            format!("(+ {} {})", balanced_sum(left), balanced_sum(right))
+        }
+    }
+}
+
+/// A balanced sum of `n` ones, to see whether parsing big programs matters.
+pub fn lambda_bench_sum(n: usize) -> Bench<Lambda, LambdaAnalysis> {
+    let ones = vec!["1".to_string(); n];
+    program_bench(&format!("sum{}", n), &balanced_sum(&ones)).with_tags(&["arithmetic", "scaling"])
+}
+
+/// A sum of `n` distinct free variables, to see how the analysis' `free`
+/// sets scale. Little else happens in this one, so most of its rebuild time
+/// is the analysis.
+pub fn lambda_bench_manyvars(n: usize) -> Bench<Lambda, LambdaAnalysis> {
+    let vars: Vec<String> = (0..n).map(|i| format!("(var x{})", i)).collect();
+    program_bench(&format!("manyvars{}", n), &balanced_sum(&vars))
+        .with_tags(&["analysis", "scaling"])
+}
+
+/// A balanced tree of `+` with `2^depth` leaves alternating 0 and 1, so it
+/// folds to `2^(depth - 1)` without overflowing. Nearly all the work is
+/// `modify` adding and unioning constants.
+pub fn lambda_bench_constfold(depth: u32) -> Bench<Lambda, LambdaAnalysis> {
+    let leaves: Vec<String> = (0..1usize << depth).map(|i| (i % 2).to_string()).collect();
+    program_bench(&format!("constfold{}", depth), &balanced_sum(&leaves))
+        .with_tags(&["arithmetic", "analysis"])
+}
+
+/// `n` different sums that all fold to `n`, from `(+ 1 n-1)` to `n` ones,
+/// unioned as seeds, with no rules. Saturation has nothing to do, so it's
+/// all building the e-graph and then extracting from it (see
+/// `extraction_time`).
+pub fn lambda_bench_wide(n: usize) -> Bench<Lambda, LambdaAnalysis> {
+    let encoding = |ones: usize| {
+        let mut terms = vec!["1".to_string(); ones];
+        if ones < n {
+            terms.push((n - ones).to_string());
+        }
+        balanced_sum(&terms)
+    };
+    Bench {
+        name: format!("wide{}", n),
+        rules: vec![],
+        seed_equivalences: (2..=n).map(|i| (encoding(i), encoding(1))).collect(),
+        union_seeds: true,
+        tags: vec!["extraction", "scaling"],
+        ..program_bench("wide", &encoding(1))
+    }
+}
+
+/// The benchmark the command line calls `name`, at the sizes it uses.
+pub fn bench_by_name(name: &str) -> Result<Bench<Lambda, LambdaAnalysis>, Error> {
+    Ok(match name {
+        "lambda" => lambda_bench(),
+        "lambda0" => lambda_bench0(),
+        "lambda1" => lambda_bench1(),
+        "lambda2" => lambda_bench2(),
+        "letrec" => lambda_bench_letrec(),
+        "onestep" => lambda_bench_onestep(),
+        "random" => lambda_bench_random(8, 0),
+        "sum" => lambda_bench_sum(10_000),
+        "manyvars" => lambda_bench_manyvars(1_000),
+        "constfold" => lambda_bench_constfold(12),
+        "wide" => lambda_bench_wide(1_000),
+        _ => return Err(Error::UnknownBench(name.into())),
+    })
+}
+
+/// A benchmark for the program in a `.lam` file, named after the file.
+/// Files may contain `;` line comments.
+pub fn lambda_bench_file(path: &str) -> Result<Bench<Lambda, LambdaAnalysis>, Error> {
+    let program = read_program(path)?;
+    parse_program(&program)?;
+    let name = std::path::Path::new(path)
+        .file_stem()
+        .unwrap()
+        .to_string_lossy();
+    Ok(program_bench(&name, &program))
+}
+
+impl Bench<Lambda, LambdaAnalysis> {
+    /// Checks that every start expression is closed. egg is happy with open
+    /// terms, but they can't evaluate all the way, so a free variable usually
+    /// means a typo in the program.
+    pub fn check_closed(&self) -> Result<(), String> {
+        let mut egraph = EGraph::new(self.analysis.clone());
+        for expr in &self.start_exprs {
+            let parsed: RecExpr<Lambda> = expr.parse().map_err(|e| format!("{}: {}", expr, e))?;
+            let root = egraph.add_expr(&parsed);
+            let mut free: Vec<String> = egraph[root]
+                .data
+                .free
+                .iter()
+                .flat_map(|&v| egraph[v].nodes.iter())
+                .filter_map(|node| match node {
+                    Lambda::Symbol(s) => Some(s.to_string()),
+                    _ => None,
+                })
+                .collect();
+            if !free.is_empty() {
+                free.sort();
+                return Err(format!("free variables in {}: {}", expr, free.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every variable in the start expressions, bound or used,
+    /// is a symbol. A variable named like a literal, as in `(var true)`,
+    /// parses as that literal and would otherwise go unnoticed.
+    pub fn check_binders(&self) -> Result<(), String> {
+        for expr in &self.start_exprs {
+            let expr: RecExpr<Lambda> = expr.parse().map_err(|e| format!("{}: {}", expr, e))?;
+            let nodes = expr.as_ref();
+            for node in nodes {
+                let v = match node {
+                    Lambda::Var(v)
+                    | Lambda::Lambda([v, _])
+                    | Lambda::Let([v, _, _])
+                    | Lambda::LetRec([v, _, _])
+                    | Lambda::Fix([v, _]) => &nodes[usize::from(*v)],
+                    _ => continue,
+                };
+                if !matches!(v, Lambda::Symbol(_)) {
+                    return Err(format!(
+                        "the variable {} in {} parses as a literal",
+                        v, expr
+                    ));
+                }
+            }
+        }
+        Ok(())
     }
 }
 
+// `Symbol` is the catch-all, so any leaf that doesn't parse as a literal
+// becomes a symbol. Operator names like `let` or `app` are fine as variable
+// names (an operator with the wrong arity falls through to `Symbol`), but a
+// symbol that looks like a number or `true`/`false`, or that contains
+// whitespace or parens, won't survive a print/parse round trip.
+// See `fuzz/fuzz_targets/roundtrip.rs`. Such a "variable" parses as a
+// literal instead, which `Bench::check_binders` catches.
 define_language! {
     pub enum Lambda {
         Bool(bool),
-        Num(i32),
+        // i64 rather than i32 so bigger sums fit; programs written for i32
+        // mean the same, and sums that overflow still don't fold. There's no
+        // subtraction, so `-1` can only be a literal, and it prints back as
+        // `-1`; the parser tries `Num` before `Symbol`, so it never becomes a
+        // symbol either. A lone `-` is a symbol, and `(- 0 1)` doesn't parse.
+        // If `-` is ever added, its rules should normalize `(- ?a ?b)` to
+        // `(+ ?a -b)` for literal `?b` so the existing folding still applies.
+        Num(i64),
 
         "var" = Var(Id),
 
         "+" = Add([Id; 2]),
+        // a sum of any number of operands, made by `nary_add`'s flattening
+        "+*" = AddN(Box<[Id]>),
         "=" = Eq([Id; 2]),
 
         "app" = App([Id; 2]),
         "lam" = Lambda([Id; 2]),
         "let" = Let([Id; 3]),
+        // `(letrec v e body)` binds `v` in both `e` and `body`
+        "letrec" = LetRec([Id; 3]),
         "fix" = Fix([Id; 2]),
 
         "if" = If([Id; 3]),
@@ -64,18 +309,96 @@ define_language! {
 }
 
 impl Lambda {
-    fn num(&self) -> Option<i32> {
+    fn num(&self) -> Option<i64> {
         match self {
             Lambda::Num(n) => Some(*n),
             _ => None,
         }
     }
+
+    /// The operator's name in s-expressions, with `bool`, `num`, and
+    /// `symbol` standing for all the literals and symbols.
+    pub fn op_name(&self) -> &'static str {
+        match self {
+            Lambda::Bool(_) => "bool",
+            Lambda::Num(_) => "num",
+            Lambda::Var(_) => "var",
+            Lambda::Add(_) => "+",
+            Lambda::AddN(_) => "+*",
+            Lambda::Eq(_) => "=",
+            Lambda::App(_) => "app",
+            Lambda::Lambda(_) => "lam",
+            Lambda::Let(_) => "let",
+            Lambda::LetRec(_) => "letrec",
+            Lambda::Fix(_) => "fix",
+            Lambda::If(_) => "if",
+            Lambda::Symbol(_) => "symbol",
+        }
+    }
 }
 
 type EGraph = egg::EGraph<Lambda, LambdaAnalysis>;
 
-#[derive(Default, Clone)]
-pub struct LambdaAnalysis;
+/// Operators that `eval` knows how to fold, by their s-expression name.
+pub const FOLD_OPS: &[&str] = &["+", "="];
+
+#[derive(Clone)]
+pub struct LambdaAnalysis {
+    /// Operators `eval` is allowed to fold; used for ablation studies.
+    pub fold_ops: HashSet<&'static str>,
+    /// How many fresh variables capture avoidance may introduce before it
+    /// starts declining to rename.
+    pub fresh_var_limit: Option<usize>,
+    /// Fresh variables introduced so far.
+    pub fresh_vars: usize,
+    /// Whether capture avoidance declined a rename because of the limit.
+    pub fresh_var_limit_hit: bool,
+    /// Count and time calls to `make`, `merge`, and `modify` in
+    /// `analysis_stats`.
+    pub count_calls: bool,
+    /// Whether `bounded_fix` declined to unroll a fixpoint any deeper.
+    pub fix_depth_hit: bool,
+    /// Whether `modify` adds each folded constant to its e-class. Without
+    /// it constants still fold through the analysis, but no rule can match
+    /// a literal the e-graph doesn't have. See `compare_const_union`.
+    pub const_union: bool,
+}
+
+impl Default for LambdaAnalysis {
+    fn default() -> Self {
+        Self {
+            fold_ops: FOLD_OPS.iter().copied().collect(),
+            fresh_var_limit: None,
+            fresh_vars: 0,
+            fresh_var_limit_hit: false,
+            count_calls: false,
+            fix_depth_hit: false,
+            const_union: true,
+        }
+    }
+}
+
+impl LambdaAnalysis {
+    pub fn with_fold_ops<S: AsRef<str>>(ops: &[S]) -> Self {
+        for op in ops {
+            assert!(
+                FOLD_OPS.contains(&op.as_ref()),
+                "fold op should be one of {:?}, got {}",
+                FOLD_OPS,
+                op.as_ref()
+            );
+        }
+        let fold_ops = FOLD_OPS
+            .iter()
+            .copied()
+            .filter(|op| ops.iter().any(|o| o.as_ref() == *op))
+            .collect();
+        Self {
+            fold_ops,
+            ..Default::default()
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Data {
@@ -85,61 +408,762 @@ pub struct Data {
 
 fn eval(egraph: &EGraph, enode: &Lambda) -> Option<Lambda> {
     let x = |i: &Id| egraph[*i].data.constant.clone();
+    let folds = |op| egraph.analysis.fold_ops.contains(op);
     match enode {
         Lambda::Num(_) | Lambda::Bool(_) => Some(enode.clone()),
-        Lambda::Add([a, b]) => Some(Lambda::Num(x(a)?.num()? + x(b)?.num()?)),
-        Lambda::Eq([a, b]) => Some(Lambda::Bool(x(a)? == x(b)?)),
+        Lambda::Add([a, b]) if folds("+") => {
+            Some(Lambda::Num(x(a)?.num()?.checked_add(x(b)?.num()?)?))
+        }
+        Lambda::AddN(operands) if folds("+") => {
+            let sum = operands
+                .iter()
+                .try_fold(0i64, |sum, operand| sum.checked_add(x(operand)?.num()?));
+            Some(Lambda::Num(sum?))
+        }
+        Lambda::Eq([a, b]) if folds("=") => Some(Lambda::Bool(x(a)? == x(b)?)),
         _ => None,
     }
 }
 
 impl Analysis<Lambda> for LambdaAnalysis {
     type Data = Data;
+    // egg reads the result as how the merged `to` compares with `from`:
+    // `Some(Greater)` means `to` didn't change, so only the parents of the
+    // class `from` came from need their analysis redone; `Some(Less)` means
+    // `to` became `from`; `Some(Equal)` means neither changed; and `None`
+    // means `to` changed into something else, so both sides' parents are
+    // redone. Claiming `Greater` when `to` changed skips re-analysis, and
+    // constants stop propagating. Claiming `None` when nothing changed is
+    // only wasted work. The `merge_contract` test pins down each case.
     fn merge(&self, to: &mut Data, from: Data) -> Option<Ordering> {
-        let before_len = to.free.len();
-        // to.free.extend(from.free);
-        to.free.retain(|i| from.free.contains(i));
-        let did_change = before_len != to.free.len();
-        if to.constant.is_none() && from.constant.is_some() {
-            to.constant = from.constant;
-            None
-        } else if did_change {
-            None
-        } else {
-            Some(Ordering::Greater)
-        }
+        count_call(
+            self.count_calls,
+            |stats| &mut stats.merge,
+            || {
+                let before_len = to.free.len();
+                // Equivalent terms can mention different variables, as in
+                // `(if true 1 (var x))` and `1`. A variable only one of them
+                // mentions can't affect the value, so the class keeps the
+                // intersection. This is what happens when `fix` merges
+                // `(fix v body)` with its unrolling `(let v (fix v body) body)`:
+                // `make` removes `v` from both, so the class ends up with the
+                // free variables of the fixpoint either way, and an equivalent
+                // term mentioning `v` can't add it back.
+                // to.free.extend(from.free);
+                to.free.retain(|i| from.free.contains(i));
+                let did_change = before_len != to.free.len();
+                if to.constant.is_none() && from.constant.is_some() {
+                    to.constant = from.constant;
+                    None
+                } else if did_change {
+                    None
+                } else {
+                    Some(Ordering::Greater)
+                }
+            },
+        )
     }
 
     fn make(egraph: &EGraph, enode: &Lambda) -> Data {
-        let f = |i: &Id| egraph[*i].data.free.iter().cloned();
-        let mut free = HashSet::default();
-        match enode {
-            Lambda::Var(v) => {
-                free.insert(*v);
+        count_call(
+            egraph.analysis.count_calls,
+            |stats| &mut stats.make,
+            || {
+                let f = |i: &Id| egraph[*i].data.free.iter().cloned();
+                let mut free = HashSet::default();
+                match enode {
+                    Lambda::Var(v) => {
+                        free.insert(*v);
+                    }
+                    Lambda::Let([v, a, b]) => {
+                        free.extend(f(b));
+                        free.remove(v);
+                        free.extend(f(a));
+                    }
+                    Lambda::Lambda([v, a]) | Lambda::Fix([v, a]) => {
+                        free.extend(f(a));
+                        free.remove(v);
+                    }
+                    Lambda::LetRec([v, a, b]) => {
+                        free.extend(f(a));
+                        free.extend(f(b));
+                        free.remove(v);
+                    }
+                    _ => enode.for_each(|c| free.extend(&egraph[c].data.free)),
+                }
+                let constant = eval(egraph, enode);
+                Data { constant, free }
+            },
+        )
+    }
+
+    fn modify(egraph: &mut EGraph, id: Id) {
+        let count_calls = egraph.analysis.count_calls;
+        count_call(
+            count_calls,
+            |stats| &mut stats.modify,
+            || {
+                if !egraph.analysis.const_union {
+                    return;
+                }
+                if let Some(c) = egraph[id].data.constant.clone() {
+                    let const_id = egraph.add(c);
+                    egraph.union(id, const_id);
+                }
+            },
+        )
+    }
+}
+
+thread_local! {
+    static ANALYSIS_STATS: std::cell::RefCell<AnalysisStats> = Default::default();
+}
+
+/// The calls to `LambdaAnalysis` on this thread with `count_calls` set,
+/// since the last `reset_analysis_stats`. `modify` adds e-nodes, so its
+/// time includes the `make` calls that makes.
+pub fn analysis_stats() -> AnalysisStats {
+    ANALYSIS_STATS.with(|stats| stats.borrow().clone())
+}
+
+pub fn reset_analysis_stats() {
+    ANALYSIS_STATS.with(|stats| *stats.borrow_mut() = Default::default());
+}
+
+/// Checks `stats` could have come from building an e-graph of `nodes` e-nodes
+/// with merges: every e-node was made at least once, and each hook ran.
+pub fn check_analysis_stats(stats: &AnalysisStats, nodes: usize) -> Result<(), String> {
+    if stats.make.calls < nodes {
+        return Err(format!(
+            "make was called {} times for {} e-nodes",
+            stats.make.calls, nodes
+        ));
+    }
+    for (hook, call) in &[("merge", &stats.merge), ("modify", &stats.modify)] {
+        if call.calls == 0 {
+            return Err(format!("{} was never called", hook));
+        }
+    }
+    Ok(())
+}
+
+/// How many e-nodes in `egraph` have each operator, by `op_name`, to see
+/// what a saturated e-graph is full of.
+pub fn operator_counts(egraph: &EGraph) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for class in egraph.classes() {
+        for node in &class.nodes {
+            *counts.entry(node.op_name()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// Checks `counts` from `operator_counts` accounts for all `nodes` e-nodes.
+pub fn check_operator_counts(counts: &BTreeMap<&str, usize>, nodes: usize) -> Result<(), String> {
+    let counted: usize = counts.values().sum();
+    if counted == nodes {
+        Ok(())
+    } else {
+        Err(format!(
+            "counted {} operators for {} e-nodes",
+            counted, nodes
+        ))
+    }
+}
+
+fn count_call<T>(
+    enabled: bool,
+    which: fn(&mut AnalysisStats) -> &mut CallStats,
+    f: impl FnOnce() -> T,
+) -> T {
+    if !enabled {
+        return f();
+    }
+    let start = std::time::Instant::now();
+    let result = f();
+    let time = start.elapsed().as_secs_f64();
+    ANALYSIS_STATS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        let call = which(&mut stats);
+        call.calls += 1;
+        call.time += time;
+    });
+    result
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonENode {
+    op: String,
+    children: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonEClass {
+    id: usize,
+    nodes: Vec<JsonENode>,
+    free: usize,
+    constant: Option<String>,
+}
+
+/// Serializes `egraph` as a list of e-classes, sorted by canonical id, each
+/// with its e-nodes (operator and child e-class ids) and a summary of its
+/// analysis data.
+pub fn export_egraph_json(egraph: &EGraph) -> String {
+    let mut classes: Vec<JsonEClass> = egraph
+        .classes()
+        .map(|class| JsonEClass {
+            id: class.id.into(),
+            nodes: class
+                .nodes
+                .iter()
+                .map(|node| JsonENode {
+                    op: node.to_string(),
+                    children: node
+                        .children()
+                        .iter()
+                        .map(|&c| egraph.find(c).into())
+                        .collect(),
+                })
+                .collect(),
+            free: class.data.free.len(),
+            constant: class.data.constant.as_ref().map(|c| c.to_string()),
+        })
+        .collect();
+    classes.sort_by_key(|class| class.id);
+    serde_json::to_string_pretty(&classes).unwrap()
+}
+
+/// Saturates `expr` with `rules()` and extracts the smallest equivalent term.
+/// Fails if the run hit one of egg's default limits, as the term it found
+/// then may not be the smallest.
+pub fn simplify(expr: &RecExpr<Lambda>) -> Result<RecExpr<Lambda>, Error> {
+    let runner = Runner::default().with_expr(expr).run(&rules());
+    match &runner.stop_reason {
+        Some(StopReason::Saturated) => (),
+        reason => return Err(Error::LimitExceeded(format!("{:?}", reason))),
+    }
+    let extractor = Extractor::new(&runner.egraph, AstSize);
+    Ok(extractor.find_best(runner.roots[0]).1)
+}
+
+/// How a bench fared with `modify`'s constant union on (eager) and off
+/// (lazy), from `compare_const_union`. The deltas are lazy minus eager.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConstUnionComparison {
+    pub eager: BenchReport,
+    pub lazy: BenchReport,
+    pub iterations_delta: i64,
+    pub nodes_delta: i64,
+    pub time_delta: f64,
+    /// Whether both runs extracted the same terms.
+    pub same_result: bool,
+    /// Whether the analysis folded the roots to the same constants. A lazy
+    /// run can't extract a literal its e-graph doesn't have, so this is the
+    /// fairer comparison of what the two runs found.
+    pub same_constants: bool,
+}
+
+/// Runs `bench` with `const_union` on and then off, to test whether
+/// eagerly unioning constants speeds up convergence at the cost of a
+/// bigger e-graph.
+pub fn compare_const_union(
+    bench: &Bench<Lambda, LambdaAnalysis>,
+    config: &RunConfig,
+) -> Result<ConstUnionComparison, Error> {
+    let run = |const_union| -> Result<(BenchReport, Vec<Option<Lambda>>), Error> {
+        let mut bench = bench.clone();
+        bench.analysis.const_union = const_union;
+        let (runner, report) = bench.saturate(config)?;
+        let constants = runner
+            .roots
+            .iter()
+            .map(|&root| runner.egraph[root].data.constant.clone())
+            .collect();
+        Ok((report, constants))
+    };
+    let (eager, eager_constants) = run(true)?;
+    let (lazy, lazy_constants) = run(false)?;
+    Ok(ConstUnionComparison {
+        iterations_delta: lazy.iterations as i64 - eager.iterations as i64,
+        nodes_delta: lazy.nodes as i64 - eager.nodes as i64,
+        time_delta: lazy.total_time - eager.total_time,
+        same_result: lazy.results == eager.results,
+        same_constants: lazy_constants == eager_constants,
+        eager,
+        lazy,
+    })
+}
+
+/// An analysis that tracks nothing, to measure what `LambdaAnalysis` buys.
+#[derive(Debug, Default, Clone)]
+pub struct UnitAnalysis;
+
+impl Analysis<Lambda> for UnitAnalysis {
+    type Data = ();
+    fn merge(&self, _to: &mut (), _from: ()) -> Option<Ordering> {
+        Some(Ordering::Equal)
+    }
+    fn make(_egraph: &egg::EGraph<Lambda, Self>, _enode: &Lambda) {}
+}
+
+/// The arithmetic rules of `rules` that need no analysis, as (name, left,
+/// right), for `compare_analyses`.
+#[rustfmt::skip]
+pub const PLAIN_ARITH_RULES: &[(&str, &str, &str)] = &[
+    ("add-comm",  "(+ ?a ?b)",        "(+ ?b ?a)"),
+    ("add-assoc", "(+ (+ ?a ?b) ?c)", "(+ ?a (+ ?b ?c))"),
+    ("eq-comm",   "(= ?a ?b)",        "(= ?b ?a)"),
+    ("add-zero",  "(+ ?a 0)",         "?a"),
+];
+
+/// `rules`, given as (name, left, right), for any analysis. Conditional
+/// rules can't be written this way, because their conditions read the
+/// analysis.
+pub fn plain_rules<A: Analysis<Lambda>>(rules: &[(&str, &str, &str)]) -> Vec<Rewrite<Lambda, A>> {
+    rules
+        .iter()
+        .map(|(name, lhs, rhs)| {
+            rw!(*name;
+                { lhs.parse::<Pattern<Lambda>>().unwrap() } =>
+                { rhs.parse::<Pattern<Lambda>>().unwrap() })
+        })
+        .collect()
+}
+
+/// How the same program and rules fared under two analyses, from
+/// `compare_analyses`. The deltas are `b` minus `a`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisComparison {
+    pub a: BenchReport,
+    pub b: BenchReport,
+    pub iterations_delta: i64,
+    pub nodes_delta: i64,
+    pub classes_delta: i64,
+    pub best_cost_delta: i64,
+}
+
+/// Runs `expr` with `rules` under `analysis_a` and then `analysis_b`. The
+/// rules are given as patterns, as in `plain_rules`, since egg ties each
+/// rewrite to one analysis.
+pub fn compare_analyses<A, B>(
+    expr: &str,
+    rules: &[(&str, &str, &str)],
+    analysis_a: A,
+    analysis_b: B,
+    config: &RunConfig,
+) -> Result<AnalysisComparison, Error>
+where
+    A: Analysis<Lambda> + Default + Clone + 'static,
+    A::Data: Clone,
+    B: Analysis<Lambda> + Default + Clone + 'static,
+    B::Data: Clone,
+{
+    fn bench<A: Analysis<Lambda> + 'static>(
+        expr: &str,
+        rules: &[(&str, &str, &str)],
+        analysis: A,
+    ) -> Bench<Lambda, A> {
+        Bench {
+            name: "compare".into(),
+            start_exprs: vec![expr.into()],
+            analysis,
+            rules: plain_rules(rules),
+            bench_pats: vec![],
+            compiled_pats: Default::default(),
+            pattern_compiles: Default::default(),
+            seed_equivalences: vec![],
+            union_seeds: true,
+            assumptions: vec![],
+            tags: vec![],
+        }
+    }
+    let a = bench(expr, rules, analysis_a).run(config)?;
+    let b = bench(expr, rules, analysis_b).run(config)?;
+    Ok(AnalysisComparison {
+        iterations_delta: b.iterations as i64 - a.iterations as i64,
+        nodes_delta: b.nodes as i64 - a.nodes as i64,
+        classes_delta: b.classes as i64 - a.classes as i64,
+        best_cost_delta: b.best_cost as i64 - a.best_cost as i64,
+        a,
+        b,
+    })
+}
+
+/// The `program => result` lines of the corpus at `path`, with their line
+/// numbers, and a message for each line that isn't one.
+fn read_corpus(path: &str) -> (Vec<(usize, RecExpr<Lambda>, RecExpr<Lambda>)>, Vec<String>) {
+    let corpus = read_program(path).unwrap_or_else(|e| panic!("failed to read {}: {}", path, e));
+    let mut cases = vec![];
+    let mut failures = vec![];
+    for (i, line) in corpus.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.find("=>") {
+            Some(arrow) => {
+                let program = parse_program(line[..arrow].trim());
+                let expected = parse_program(line[arrow + 2..].trim());
+                match (program, expected) {
+                    (Ok(program), Ok(expected)) => cases.push((i + 1, program, expected)),
+                    (Err(e), _) | (_, Err(e)) => {
+                        failures.push(format!("{}:{}: {}", path, i + 1, e))
+                    }
+                }
+            }
+            None => failures.push(format!("{}:{}: expected `program => result`", path, i + 1)),
+        }
+    }
+    (cases, failures)
+}
+
+/// Checks each `program => result` line of the corpus at `path` (see
+/// `corpus.txt`): the rules have to prove the program equal to the result,
+/// or simplify the two to the same term up to renaming bound variables.
+/// Returns a message for every case that fails.
+pub fn check_corpus(path: &str) -> Result<(), Vec<String>> {
+    let (cases, mut failures) = read_corpus(path);
+    for (line, program, expected) in cases {
+        let runner = Runner::default()
+            .with_expr(&program)
+            .with_expr(&expected)
+            .run(&rules());
+        let (root, expected_root) = (runner.roots[0], runner.roots[1]);
+        if runner.egraph.find(root) == runner.egraph.find(expected_root) {
+            continue;
+        }
+        let extractor = Extractor::new(&runner.egraph, AstSize);
+        let actual = extractor.find_best(root).1;
+        if alpha_canonical(&actual) != alpha_canonical(&extractor.find_best(expected_root).1) {
+            failures.push(format!(
+                "{}:{}: {} simplified to {}, expected {}",
+                path, line, program, actual, expected
+            ));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// The smallest term of each of `roots`, alpha-normalized and pretty
+/// printed, a line (or more) each, for comparing against golden files.
+pub fn extracted_canonical(egraph: &EGraph, roots: &[Id]) -> String {
+    let extractor = Extractor::new(egraph, AstSize);
+    roots
+        .iter()
+        .map(|&root| alpha_canonical(&extractor.find_best(root).1).pretty(80) + "\n")
+        .collect()
+}
+
+/// Checks `actual` against the golden file at `path`, or (re)writes the
+/// file with it when `update` is set. A mismatch comes back as a diff with
+/// the golden lines marked `-` and the actual ones `+`.
+pub fn check_golden(
+    actual: &str,
+    path: impl AsRef<std::path::Path>,
+    update: bool,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    if update {
+        return std::fs::write(path, actual)
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e));
+    }
+    let expected = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    if expected == actual {
+        return Ok(());
+    }
+    let (expected, actual): (Vec<_>, Vec<_>) =
+        (expected.lines().collect(), actual.lines().collect());
+    let mut diff = format!("{} doesn't match:\n", path.display());
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => diff.push_str(&format!("  {}\n", e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    diff.push_str(&format!("- {}\n", e));
+                }
+                if let Some(a) = a {
+                    diff.push_str(&format!("+ {}\n", a));
+                }
+            }
+        }
+    }
+    Err(diff)
+}
+
+/// Panics unless what `bench` simplifies to under `config` matches the
+/// golden file at `path` (see `check_golden`), or rewrites the file with
+/// it when `update` is set.
+pub fn assert_extracted_matches_golden(
+    bench: &Bench<Lambda, LambdaAnalysis>,
+    config: &RunConfig,
+    path: impl AsRef<std::path::Path>,
+    update: bool,
+) {
+    let (runner, _) = bench
+        .saturate(config)
+        .unwrap_or_else(|e| panic!("failed to run {}: {}", bench.name, e));
+    let actual = extracted_canonical(&runner.egraph, &runner.roots);
+    if let Err(diff) = check_golden(&actual, path, update) {
+        panic!("{}", diff);
+    }
+}
+
+/// Panics unless the analysis folded `root` to the literal `expected`,
+/// showing the best term it could find instead.
+pub fn assert_result_constant(egraph: &EGraph, root: Id, expected: &Lambda) {
+    let constant = egraph[root].data.constant.as_ref();
+    match constant {
+        Some(c) if c == expected => (),
+        Some(c) => panic!("expected {}, folded to {} instead", expected, c),
+        None => {
+            let (_, best) = Extractor::new(egraph, AstSize).find_best(root);
+            panic!("expected {}, didn't fold; best term is {}", expected, best)
+        }
+    }
+}
+
+/// Writes `egraph` to `path` in the `export_egraph_json` format.
+pub fn save_egraph(egraph: &EGraph, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    std::fs::write(path, export_egraph_json(egraph))
+}
+
+/// Rebuilds an e-graph saved by `save_egraph`, along with a map from the saved
+/// e-class ids to the new ones. The analysis data isn't read back, as the
+/// file only has a summary of it. `analysis` recomputes it as the e-nodes are
+/// added, so it should be set up like the one that was saved for the data to
+/// come out the same.
+pub fn load_egraph(
+    path: impl AsRef<std::path::Path>,
+    analysis: LambdaAnalysis,
+) -> Result<(EGraph, HashMap<usize, Id>), Error> {
+    let json = std::fs::read_to_string(path)?;
+    let classes: Vec<JsonEClass> =
+        serde_json::from_str(&json).map_err(|e| Error::Parse(e.to_string()))?;
+    let mut egraph = EGraph::new(analysis);
+    let mut ids: HashMap<usize, Id> = HashMap::new();
+    let mut pending: Vec<(usize, &JsonENode)> = classes
+        .iter()
+        .flat_map(|class| class.nodes.iter().map(move |node| (class.id, node)))
+        .collect();
+    // every e-class has an e-node whose children were in the e-graph before
+    // it, the first one added, so adding whatever is ready makes progress
+    while !pending.is_empty() {
+        let mut blocked = vec![];
+        for &(class, node) in &pending {
+            let children: Option<Vec<Id>> =
+                node.children.iter().map(|c| ids.get(c).copied()).collect();
+            let children = match children {
+                Some(children) => children,
+                None => {
+                    blocked.push((class, node));
+                    continue;
+                }
+            };
+            let id = egraph.add(node_from_json(&node.op, &children).map_err(Error::Parse)?);
+            match ids.get(&class) {
+                Some(&existing) => {
+                    egraph.union(existing, id);
+                }
+                None => {
+                    ids.insert(class, id);
+                }
             }
-            Lambda::Let([v, a, b]) => {
-                free.extend(f(b));
-                free.remove(v);
-                free.extend(f(a));
+        }
+        if blocked.len() == pending.len() {
+            let classes: BTreeSet<usize> = blocked.iter().map(|(class, _)| *class).collect();
+            return Err(Error::Parse(format!(
+                "e-classes {:?} can't be built from the others",
+                classes
+            )));
+        }
+        pending = blocked;
+    }
+    egraph.rebuild();
+    Ok((egraph, ids))
+}
+
+/// The e-node `op` prints as, parsed like `define_language!` parses it.
+fn node_from_json(op: &str, children: &[Id]) -> Result<Lambda, String> {
+    Ok(match (op, children) {
+        ("+*", _) => Lambda::AddN(children.into()),
+        ("var", &[v]) => Lambda::Var(v),
+        ("+", &[a, b]) => Lambda::Add([a, b]),
+        ("=", &[a, b]) => Lambda::Eq([a, b]),
+        ("app", &[f, x]) => Lambda::App([f, x]),
+        ("lam", &[v, body]) => Lambda::Lambda([v, body]),
+        ("let", &[v, e, body]) => Lambda::Let([v, e, body]),
+        ("letrec", &[v, e, body]) => Lambda::LetRec([v, e, body]),
+        ("fix", &[v, body]) => Lambda::Fix([v, body]),
+        ("if", &[c, then, els]) => Lambda::If([c, then, els]),
+        (_, []) => match (op.parse(), op.parse()) {
+            (Ok(b), _) => Lambda::Bool(b),
+            (_, Ok(n)) => Lambda::Num(n),
+            _ => Lambda::Symbol(op.into()),
+        },
+        _ => return Err(format!("{} can't have {} children", op, children.len())),
+    })
+}
+
+/// How many children `op` takes at the head of a list, or `None` for any
+/// number. Literals and symbols take none.
+fn expected_children(op: &str) -> Option<usize> {
+    match op {
+        "+*" => None,
+        "var" => Some(1),
+        "+" | "=" | "app" | "lam" | "fix" => Some(2),
+        "let" | "letrec" | "if" => Some(3),
+        _ => Some(0),
+    }
+}
+
+/// An open list in `check_arity`.
+struct Frame {
+    op: Option<String>,
+    children: usize,
+    line: usize,
+    column: usize,
+}
+
+/// Checks every list in the s-expression `src` is an operator with the
+/// right number of children, so that a malformed program like `(+ 1)` gets
+/// an error naming the operator and where it is rather than egg's generic
+/// parse error. Positions are the list's open paren, as `line:column`.
+pub fn check_arity(src: &str) -> Result<(), String> {
+    let mut frames: Vec<Frame> = vec![];
+    let mut atom = String::new();
+    let (mut line, mut column) = (1, 0);
+    // a trailing space ends the last atom
+    for c in src.chars().chain(std::iter::once(' ')) {
+        if c == '\n' {
+            line += 1;
+            column = 0;
+        } else {
+            column += 1;
+        }
+        if !(c.is_whitespace() || c == '(' || c == ')') {
+            atom.push(c);
+            continue;
+        }
+        if !atom.is_empty() {
+            if let Some(frame) = frames.last_mut() {
+                match frame.op {
+                    None => frame.op = Some(atom.clone()),
+                    Some(_) => frame.children += 1,
+                }
+            }
+            atom.clear();
+        }
+        match c {
+            '(' => {
+                if let Some(Frame { op: None, .. }) = frames.last() {
+                    return Err(format!("expected an operator at {}:{}", line, column));
+                }
+                frames.push(Frame {
+                    op: None,
+                    children: 0,
+                    line,
+                    column,
+                })
             }
-            Lambda::Lambda([v, a]) | Lambda::Fix([v, a]) => {
-                free.extend(f(a));
-                free.remove(v);
+            ')' => {
+                let frame = frames
+                    .pop()
+                    .ok_or_else(|| format!("unmatched ) at {}:{}", line, column))?;
+                let op = frame
+                    .op
+                    .ok_or_else(|| format!("empty list at {}:{}", frame.line, frame.column))?;
+                match expected_children(&op) {
+                    Some(n) if n != frame.children => {
+                        return Err(format!(
+                            "`{}` at {}:{} takes {} children, got {}",
+                            op, frame.line, frame.column, n, frame.children
+                        ))
+                    }
+                    _ => (),
+                }
+                if let Some(parent) = frames.last_mut() {
+                    parent.children += 1;
+                }
             }
-            _ => enode.for_each(|c| free.extend(&egraph[c].data.free)),
+            _ => (),
         }
-        let constant = eval(egraph, enode);
-        Data { constant, free }
     }
+    match frames.last() {
+        Some(frame) => Err(format!("unclosed ( at {}:{}", frame.line, frame.column)),
+        None => Ok(()),
+    }
+}
 
-    fn modify(egraph: &mut EGraph, id: Id) {
-        if let Some(c) = egraph[id].data.constant.clone() {
-            let const_id = egraph.add(c);
-            egraph.union(id, const_id);
+/// Parses a lambda program from untrusted input, like a file, checking
+/// arity first for a better error than egg gives.
+pub fn parse_program(src: &str) -> Result<RecExpr<Lambda>, Error> {
+    check_arity(src).map_err(Error::Parse)?;
+    src.parse()
+        .map_err(|e| Error::Parse(format!("{}: {}", src, e)))
+}
+
+/// `AstSize`, except that literals are free. The analysis adds the literal
+/// to every e-class it folds to a constant, so extracting with this picks
+/// `3` for `(+ 1 2)` instead of breaking the tie some other way, and leaves
+/// classes without a constant as they'd be under `AstSize`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferConstants;
+
+impl CostFunction<Lambda> for PreferConstants {
+    type Cost = usize;
+    fn cost<C>(&mut self, enode: &Lambda, mut costs: C) -> usize
+    where
+        C: FnMut(Id) -> usize,
+    {
+        match enode {
+            Lambda::Bool(_) | Lambda::Num(_) => 0,
+            _ => enode.fold(1, |sum, id| sum + costs(id)),
         }
     }
 }
 
+/// `AstSize` with a weight for each operator, by `Lambda::op_name`.
+/// Operators without a weight weigh 1.
+#[derive(Debug, Clone, Default)]
+pub struct WeightedCost {
+    pub weights: HashMap<String, u64>,
+}
+
+impl CostFunction<Lambda> for WeightedCost {
+    type Cost = u64;
+    fn cost<C>(&mut self, enode: &Lambda, mut costs: C) -> u64
+    where
+        C: FnMut(Id) -> u64,
+    {
+        let weight = self.weights.get(enode.op_name()).copied().unwrap_or(1);
+        enode.fold(weight, |sum, id| sum.saturating_add(costs(id)))
+    }
+}
+
+/// Parses weights like `lam=100,app=2`.
+impl std::str::FromStr for WeightedCost {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = HashMap::new();
+        for entry in s.split(',').filter(|entry| !entry.is_empty()) {
+            let mut parts = entry.splitn(2, '=');
+            let op = parts.next().unwrap().trim();
+            let weight = parts
+                .next()
+                .and_then(|w| w.trim().parse().ok())
+                .ok_or_else(|| format!("weight should look like op=1, got {}", entry))?;
+            weights.insert(op.to_string(), weight);
+        }
+        Ok(Self { weights })
+    }
+}
+
 fn var(s: &str) -> Var {
     s.parse().unwrap()
 }
@@ -148,47 +1172,655 @@ fn is_not_same_var(v1: Var, v2: Var) -> impl Fn(&mut EGraph, Id, &Subst) -> bool
     move |egraph, _, subst| egraph.find(subst[v1]) != egraph.find(subst[v2])
 }
 
+/// Whether `v` is free in `body` but not in `e`.
+fn is_only_free_in(v: Var, body: Var, e: Var) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    move |egraph, _, subst| {
+        let v = subst[v];
+        egraph[subst[body]].data.free.contains(&v) && !egraph[subst[e]].data.free.contains(&v)
+    }
+}
+
 fn is_const(v: Var) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     move |egraph, _, subst| egraph[subst[v]].data.constant.is_some()
 }
 
-fn rules() -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+/// How deep `eval_under_let` looks into a condition.
+const LET_COND_FUEL: usize = 16;
+
+/// Evaluates `id` like `eval` does, additionally reading `(var v)` as
+/// `value`. Only arithmetic and comparisons are looked through, so no binder
+/// can shadow `v`. Results are kept in `memo` by e-class and `v`, so an
+/// e-class shared by many operands is evaluated once. An e-class is entered
+/// as `None` while it's being evaluated, so a cycle back to it gives up.
+fn eval_under_let(
+    egraph: &EGraph,
+    v: Id,
+    value: &Lambda,
+    id: Id,
+    fuel: usize,
+    memo: &mut HashMap<(Id, Id), Option<Lambda>>,
+) -> Option<Lambda> {
+    let id = egraph.find(id);
+    if let Some(c) = &egraph[id].data.constant {
+        return Some(c.clone());
+    }
+    if let Some(known) = memo.get(&(id, v)) {
+        return known.clone();
+    }
+    let fuel = fuel.checked_sub(1)?;
+    memo.insert((id, v), None);
+    let mut x = |i: &Id| eval_under_let(egraph, v, value, *i, fuel, memo);
+    let folds = |op| egraph.analysis.fold_ops.contains(op);
+    let result = egraph[id].nodes.iter().find_map(|node| match node {
+        Lambda::Var(w) if egraph.find(*w) == v => Some(value.clone()),
+        Lambda::Add([a, b]) if folds("+") => {
+            Some(Lambda::Num(x(a)?.num()?.checked_add(x(b)?.num()?)?))
+        }
+        Lambda::Eq([a, b]) if folds("=") => Some(Lambda::Bool(x(a)? == x(b)?)),
+        _ => None,
+    });
+    memo.insert((id, v), result.clone());
+    result
+}
+
+/// Whether `cond` evaluates to `b` once `v` is bound to the constant `e`.
+fn let_cond_is(v: Var, e: Var, cond: Var, b: bool) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    move |egraph, _, subst| {
+        let value = match &egraph[subst[e]].data.constant {
+            Some(c) => c.clone(),
+            None => return false,
+        };
+        let v = egraph.find(subst[v]);
+        let result = eval_under_let(
+            egraph,
+            v,
+            &value,
+            subst[cond],
+            LET_COND_FUEL,
+            &mut HashMap::new(),
+        );
+        result == Some(Lambda::Bool(b))
+    }
+}
+
+/// Which theory a rule belongs to, so benchmarks can focus on one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleTag {
+    Arith,
+    Subst,
+    Control,
+}
+
+impl std::str::FromStr for RuleTag {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "arith" => Ok(RuleTag::Arith),
+            "subst" => Ok(RuleTag::Subst),
+            "control" => Ok(RuleTag::Control),
+            _ => Err(format!(
+                "rule tag should be one of arith, subst, or control, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// The source of a rule made with `rule!`: its left side, and its right
+/// side if it unconditionally rewrites to a pattern.
+#[derive(Debug, Clone, Copy)]
+struct RuleSource {
+    lhs: &'static str,
+    rhs: Option<&'static str>,
+}
+
+/// `rw!`, paired with the source of the rule's sides, so `rule_lhs` and
+/// `bidirectional` can read each rule's patterns from where the rule is
+/// written.
+macro_rules! rule {
+    ($name:expr; $lhs:literal => $rhs:literal) => {
+        (RuleSource { lhs: $lhs, rhs: Some($rhs) }, rw!($name; $lhs => $rhs))
+    };
+    ($name:expr; $lhs:literal => $($rhs:tt)+) => {
+        (RuleSource { lhs: $lhs, rhs: None }, rw!($name; $lhs => $($rhs)+))
+    };
+}
+
+#[rustfmt::skip]
+fn tagged_rules() -> Vec<(RuleTag, (RuleSource, Rewrite<Lambda, LambdaAnalysis>))> {
+    use RuleTag::*;
     vec![
         // open term rules
-        rw!("if-true";  "(if  true ?then ?else)" => "?then"),
-        rw!("if-false"; "(if false ?then ?else)" => "?else"),
-        rw!("if-elim"; "(if (= (var ?x) ?e) ?then ?else)" => "?else"
-            if ConditionEqual::parse("(let ?x ?e ?then)", "(let ?x ?e ?else)")),
-        rw!("add-comm";  "(+ ?a ?b)"        => "(+ ?b ?a)"),
-        rw!("add-assoc"; "(+ (+ ?a ?b) ?c)" => "(+ ?a (+ ?b ?c))"),
-        rw!("eq-comm";   "(= ?a ?b)"        => "(= ?b ?a)"),
+        (Control, rule!("if-true";  "(if  true ?then ?else)" => "?then")),
+        (Control, rule!("if-false"; "(if false ?then ?else)" => "?else")),
+        (Control, rule!("if-elim"; "(if (= (var ?x) ?e) ?then ?else)" => "?else"
+            if ConditionEqual::parse("(let ?x ?e ?then)", "(let ?x ?e ?else)"))),
+        // In the then branch `?x` is known to equal `?e`, so rebinding `?x`
+        // to `?e` there is sound: the let evaluates `?e` outside the branch,
+        // where it has the same value as `?x`. Only variables can be
+        // rewritten this way, as a let can't bind an arbitrary term. Once
+        // `?x` is bound it's no longer free in the new branch, unless `?e`
+        // mentions it, when the rule would fire on its own output forever,
+        // so it only fires when `?x` isn't free in `?e`.
+        (Control, rule!("if-eq-then";
+            "(if (= (var ?x) ?e) ?then ?else)" => "(if (= (var ?x) ?e) (let ?x ?e ?then) ?else)"
+            if is_only_free_in(var("?x"), var("?then"), var("?e")))),
+        // Evaluation is pure, so applying the branch taken to the argument
+        // is the same as applying the if: the argument is evaluated once
+        // either way, and a stuck condition is stuck on both sides.
+        (Control, rule!("app-if";
+            "(app (if ?c ?f ?g) ?x)" => "(if ?c (app ?f ?x) (app ?g ?x))")),
+        (Arith, rule!("add-comm";  "(+ ?a ?b)"        => "(+ ?b ?a)")),
+        (Arith, rule!("add-assoc"; "(+ (+ ?a ?b) ?c)" => "(+ ?a (+ ?b ?c))")),
+        (Arith, rule!("eq-comm";   "(= ?a ?b)"        => "(= ?b ?a)")),
+        // a repeated pattern variable only matches the same e-class twice
+        (Arith, rule!("eq-same";   "(= ?a ?a)"        => "true")),
+        // folds sums with a symbolic operand; `x - 0` and `x - x` wait on
+        // Lambda having a `-`
+        (Arith, rule!("add-zero";  "(+ ?a 0)"         => "?a")),
         // subst rules
-        rw!("fix";      "(fix ?v ?e)"             => "(let ?v (fix ?v ?e) ?e)"),
-        rw!("beta";     "(app (lam ?v ?body) ?e)" => "(let ?v ?e ?body)"),
-        rw!("let-app";  "(let ?v ?e (app ?a ?b))" => "(app (let ?v ?e ?a) (let ?v ?e ?b))"),
-        rw!("let-add";  "(let ?v ?e (+   ?a ?b))" => "(+   (let ?v ?e ?a) (let ?v ?e ?b))"),
-        rw!("let-eq";   "(let ?v ?e (=   ?a ?b))" => "(=   (let ?v ?e ?a) (let ?v ?e ?b))"),
-        rw!("let-const";
-            "(let ?v ?e ?c)" => "?c" if is_const(var("?c"))),
-        rw!("let-if";
+        (Subst, rule!("fix";      "(fix ?v ?e)"             => "(let ?v (fix ?v ?e) ?e)")),
+        (Subst, rule!("letrec";   "(letrec ?v ?e ?body)"    => "(let ?v (fix ?v ?e) ?body)")),
+        (Subst, rule!("beta";     "(app (lam ?v ?body) ?e)" => "(let ?v ?e ?body)")),
+        (Subst, rule!("let-app";  "(let ?v ?e (app ?a ?b))" => "(app (let ?v ?e ?a) (let ?v ?e ?b))")),
+        (Subst, rule!("let-add";  "(let ?v ?e (+   ?a ?b))" => "(+   (let ?v ?e ?a) (let ?v ?e ?b))")),
+        (Subst, rule!("let-eq";   "(let ?v ?e (=   ?a ?b))" => "(=   (let ?v ?e ?a) (let ?v ?e ?b))")),
+        (Subst, rule!("let-const";
+            "(let ?v ?e ?c)" => "?c" if is_const(var("?c")))),
+        (Subst, rule!("let-if";
             "(let ?v ?e (if ?cond ?then ?else))" =>
             "(if (let ?v ?e ?cond) (let ?v ?e ?then) (let ?v ?e ?else))"
-        ),
-        rw!("let-var-same"; "(let ?v1 ?e (var ?v1))" => "?e"),
-        rw!("let-var-diff"; "(let ?v1 ?e (var ?v2))" => "(var ?v2)"
-            if is_not_same_var(var("?v1"), var("?v2"))),
-        rw!("let-lam-same"; "(let ?v1 ?e (lam ?v1 ?body))" => "(lam ?v1 ?body)"),
-        rw!("let-lam-diff";
+        )),
+        // decide the condition without distributing the let first
+        (Control, rule!("let-if-true";
+            "(let ?v ?e (if ?cond ?then ?else))" => "(let ?v ?e ?then)"
+            if let_cond_is(var("?v"), var("?e"), var("?cond"), true))),
+        (Control, rule!("let-if-false";
+            "(let ?v ?e (if ?cond ?then ?else))" => "(let ?v ?e ?else)"
+            if let_cond_is(var("?v"), var("?e"), var("?cond"), false))),
+        (Subst, rule!("let-var-same"; "(let ?v1 ?e (var ?v1))" => "?e")),
+        (Subst, rule!("let-var-diff"; "(let ?v1 ?e (var ?v2))" => "(var ?v2)"
+            if is_not_same_var(var("?v1"), var("?v2")))),
+        (Subst, rule!("let-lam-same"; "(let ?v1 ?e (lam ?v1 ?body))" => "(lam ?v1 ?body)")),
+        (Subst, rule!("let-lam-diff";
             "(let ?v1 ?e (lam ?v2 ?body))" =>
             { CaptureAvoid {
                 fresh: var("?fresh"), v2: var("?v2"), e: var("?e"),
                 if_not_free: "(lam ?v2 (let ?v1 ?e ?body))".parse().unwrap(),
                 if_free: "(lam ?fresh (let ?v1 ?e (let ?v2 (var ?fresh) ?body)))".parse().unwrap(),
             }}
-            if is_not_same_var(var("?v1"), var("?v2"))),
+            if is_not_same_var(var("?v1"), var("?v2")))),
+    ]
+}
+
+pub fn rules() -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    tagged_rules()
+        .into_iter()
+        .map(|(_, (_, rule))| rule)
+        .collect()
+}
+
+/// The rules tagged with any of `tags`.
+pub fn rules_for(tags: &[RuleTag]) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    tagged_rules()
+        .into_iter()
+        .filter(|(tag, _)| tags.contains(tag))
+        .map(|(_, (_, rule))| rule)
+        .collect()
+}
+
+/// What a rule in `rules` is for, for tooling that explains or checks rules.
+#[derive(Debug, Clone)]
+pub struct RuleMeta {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub tag: RuleTag,
+    /// Benches (by `bench_by_name` name) whose e-graphs the rule's left side
+    /// should match once they've run.
+    pub expected_to_fire_on: Vec<&'static str>,
+}
+
+/// Metadata for each rule in `rules`, in the same order. Keep in sync with
+/// `tagged_rules`.
+#[rustfmt::skip]
+pub fn rule_meta() -> Vec<RuleMeta> {
+    use RuleTag::*;
+    let meta = |name, tag, description, expected_to_fire_on: &[&'static str]| RuleMeta {
+        name, description, tag, expected_to_fire_on: expected_to_fire_on.to_vec(),
+    };
+    vec![
+        meta("if-true", Control, "takes the then branch of an if on true", &["lambda0"]),
+        meta("if-false", Control, "takes the else branch of an if on false", &["lambda0"]),
+        meta("if-elim", Control,
+            "drops an if on x = e whose branches agree once x is bound to e", &["lambda0"]),
+        meta("if-eq-then", Control,
+            "binds x to e in the then branch of an if on x = e", &["lambda0"]),
+        meta("app-if", Control,
+            "applies both branches of an applied if, exposing beta redexes", &["lambda1"]),
+        meta("add-comm", Arith, "commutes a sum", &["lambda0"]),
+        meta("add-assoc", Arith, "reassociates a sum to the right", &["constfold"]),
+        meta("eq-comm", Arith, "commutes an equality", &["lambda0"]),
+        meta("eq-same", Arith, "decides an equality of an e-class with itself", &["lambda0"]),
+        meta("add-zero", Arith, "drops a zero operand of a sum", &["lambda0"]),
+        meta("fix", Subst, "unrolls a fixpoint one level", &["lambda1", "lambda2"]),
+        meta("letrec", Subst, "desugars letrec into let and fix", &["letrec"]),
+        meta("beta", Subst, "turns an application of a lambda into a let", &["lambda0", "lambda1"]),
+        meta("let-app", Subst, "pushes a let into an application", &["lambda0"]),
+        meta("let-add", Subst, "pushes a let into a sum", &["lambda0"]),
+        meta("let-eq", Subst, "pushes a let into an equality", &["lambda0"]),
+        meta("let-const", Subst, "drops a let around a constant", &["lambda0"]),
+        meta("let-if", Subst, "pushes a let into an if's condition and branches", &["lambda0"]),
+        meta("let-if-true", Control,
+            "takes the then branch of an if under a let whose condition folds to true",
+            &["lambda0"]),
+        meta("let-if-false", Control,
+            "takes the else branch of an if under a let whose condition folds to false",
+            &["lambda0"]),
+        meta("let-var-same", Subst, "substitutes into the variable the let binds", &["lambda0"]),
+        meta("let-var-diff", Subst, "drops a let around a different variable", &["lambda1"]),
+        meta("let-lam-same", Subst, "drops a let around a lambda that shadows it", &[]),
+        meta("let-lam-diff", Subst,
+            "pushes a let into a lambda, renaming the parameter if it would capture",
+            &["lambda1"]),
     ]
 }
 
+/// Checks every rule in `rules` has metadata with a description and the
+/// rule's tag, every piece of metadata is for a rule, and each rule's left
+/// side matches in the benches it's expected to fire on, after a bounded
+/// run of each.
+pub fn check_rule_meta() -> Result<(), Vec<String>> {
+    let meta = rule_meta();
+    let mut failures = vec![];
+    for (tag, (_, rule)) in tagged_rules() {
+        match meta.iter().find(|m| m.name == rule.name()) {
+            None => failures.push(format!("{} has no metadata", rule.name())),
+            Some(m) if m.description.trim().is_empty() => {
+                failures.push(format!("{} has no description", m.name))
+            }
+            Some(m) if m.tag != tag => failures.push(format!(
+                "{} is tagged {:?} but its metadata says {:?}",
+                m.name, tag, m.tag
+            )),
+            Some(_) => (),
+        }
+    }
+    let rules = rules();
+    for m in &meta {
+        let rule = match rules.iter().find(|r| r.name() == m.name) {
+            Some(rule) => rule,
+            None => {
+                failures.push(format!("metadata for {}, which isn't a rule", m.name));
+                continue;
+            }
+        };
+        for &name in &m.expected_to_fire_on {
+            let bench = match bench_by_name(name) {
+                Ok(bench) => bench,
+                Err(e) => {
+                    failures.push(format!("{}: {}", m.name, e));
+                    continue;
+                }
+            };
+            let mut runner = Runner::default()
+                .with_iter_limit(30)
+                .with_node_limit(10_000);
+            for expr in &bench.start_exprs {
+                runner = runner.with_expr(&expr.parse().unwrap());
+            }
+            let runner = runner.run(&bench.rules);
+            if rule.search(&runner.egraph).is_empty() {
+                failures.push(format!("{} doesn't match in {}", m.name, name));
+            }
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// The unconditional rules with a useful reverse, that is, leaving out rules
+/// whose left side has variables the right side lacks, commutativity (its
+/// own reverse), and rules rewriting to just a variable, whose reverse would
+/// match every e-class.
+const REVERSIBLE_RULES: &[&str] = &[
+    "add-assoc",
+    "app-if",
+    "fix",
+    "beta",
+    "let-app",
+    "let-add",
+    "let-eq",
+    "let-if",
+];
+
+/// The sides of each rule in `REVERSIBLE_RULES`, from `tagged_rules`.
+fn reversible_sources() -> Vec<(String, &'static str, &'static str)> {
+    tagged_rules()
+        .into_iter()
+        .filter(|(_, (_, rule))| REVERSIBLE_RULES.contains(&rule.name()))
+        .map(|(_, (source, rule))| {
+            let name = rule.name().to_string();
+            let rhs = source
+                .rhs
+                .unwrap_or_else(|| panic!("{} has no pattern to reverse", name));
+            (name, source.lhs, rhs)
+        })
+        .collect()
+}
+
+/// `rules`, plus the reverse of each one in `REVERSIBLE_RULES`, named with a
+/// `-rev` suffix. Conditional rules aren't reversed, as their conditions are
+/// about the left side.
+pub fn bidirectional(
+    rules: Vec<Rewrite<Lambda, LambdaAnalysis>>,
+) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    let sources = reversible_sources();
+    let mut reversed = vec![];
+    for rule in &rules {
+        let name = rule.name().to_string();
+        if let Some((_, lhs, rhs)) = sources.iter().find(|(n, _, _)| *n == name) {
+            reversed.push(rw!(format!("{}-rev", name);
+                { rhs.parse::<Pattern<Lambda>>().unwrap() } =>
+                { lhs.parse::<Pattern<Lambda>>().unwrap() }));
+        }
+    }
+    rules.into_iter().chain(reversed).collect()
+}
+
+/// The left side of each rule that `rules` or a rule transform like
+/// `nary_add` can make, by rule name, with runs of whitespace made one space.
+pub(crate) fn rule_lhs() -> Vec<(String, String)> {
+    let one_line = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+    let transformed = vec![
+        canonical_add(),
+        add_flatten(),
+        let_if_cond(),
+        fix_app(),
+        fix_bounded(0),
+    ];
+    let mut table: Vec<(String, String)> = tagged_rules()
+        .into_iter()
+        .map(|(_, rule)| rule)
+        .chain(transformed)
+        .map(|(source, rule)| (rule.name().to_string(), one_line(source.lhs)))
+        .collect();
+    for (name, _, rhs) in reversible_sources() {
+        table.push((format!("{}-rev", name), one_line(rhs)));
+    }
+    table
+}
+
+/// Replaces `add-comm` in `rules` (if it's there) with one that rewrites a
+/// sum to its operands, found through the binary sums under it, right-nested
+/// in e-class id order. Every permutation of a sum then meets at one form,
+/// rather than naive AC storing all of them. E-class ids shift as classes
+/// merge, so a sum can get more than one canonical form over a run, which
+/// bounds the blowup rather than removing it.
+pub fn canonical_ac(
+    rules: Vec<Rewrite<Lambda, LambdaAnalysis>>,
+) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    let len = rules.len();
+    let mut rules: Vec<_> = rules
+        .into_iter()
+        .filter(|r| r.name() != "add-comm")
+        .collect();
+    if rules.len() < len {
+        rules.push(canonical_add().1);
+    }
+    rules
+}
+
+#[rustfmt::skip]
+fn canonical_add() -> (RuleSource, Rewrite<Lambda, LambdaAnalysis>) {
+    rule!("add-comm"; "(+ ?a ?b)" => { CanonicalAdd { a: var("?a"), b: var("?b") } })
+}
+
+struct CanonicalAdd {
+    a: Var,
+    b: Var,
+}
+
+/// Pushes the operands of the sum in `id`'s e-class onto `operands`, going
+/// through the first `+` of e-classes with nothing but sums in them. Other
+/// e-classes, like a variable's or one a sum folded into a constant, are
+/// operands as they are, as is an e-class already on `path`, like the
+/// e-class of a sum `add-zero` put `(+ s 0)` in.
+fn sum_operands(egraph: &EGraph, id: Id, path: &mut Vec<Id>, operands: &mut Vec<Id>) {
+    let id = egraph.find(id);
+    let class = &egraph[id];
+    let only_sums = class
+        .nodes
+        .iter()
+        .all(|node| matches!(node, Lambda::Add(_)));
+    let sum = class.nodes.iter().find_map(|node| match node {
+        Lambda::Add(children) => Some(*children),
+        _ => None,
+    });
+    match sum {
+        Some(children) if only_sums && !path.contains(&id) => {
+            path.push(id);
+            for &child in &children {
+                sum_operands(egraph, child, path, operands);
+            }
+            path.pop();
+        }
+        _ => operands.push(id),
+    }
+}
+
+impl Applier<Lambda, LambdaAnalysis> for CanonicalAdd {
+    fn apply_one(&self, egraph: &mut EGraph, eclass: Id, subst: &Subst) -> Vec<Id> {
+        let mut path = vec![egraph.find(eclass)];
+        let mut operands = vec![];
+        sum_operands(egraph, subst[self.a], &mut path, &mut operands);
+        sum_operands(egraph, subst[self.b], &mut path, &mut operands);
+        operands.sort();
+        let last = operands.pop().unwrap();
+        let sum = operands.into_iter().rev().fold(last, |rest, operand| {
+            egraph.add(Lambda::Add([operand, rest]))
+        });
+        vec![sum]
+    }
+}
+
+/// Replaces `let-if` in `rules` (if it's there) with `let-if-cond`, which
+/// only pushes the `let` into the condition. When the condition folds,
+/// `if-true` or `if-false` leaves the `let` around just the branch taken, so
+/// the other branch is never substituted into. When it doesn't fold, the
+/// branches are never substituted into at all, so this can leave terms less
+/// simplified than `let-if` would. Pushing the `let` inside itself is only
+/// sound when its variable isn't free in the bound expression, and it's only
+/// worth it when the variable is free in the condition, which also keeps the
+/// rule from firing on its own output.
+pub fn lazy_let_if(
+    rules: Vec<Rewrite<Lambda, LambdaAnalysis>>,
+) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    let len = rules.len();
+    let mut rules: Vec<_> = rules.into_iter().filter(|r| r.name() != "let-if").collect();
+    if rules.len() < len {
+        rules.push(let_if_cond().1);
+    }
+    rules
+}
+
+#[rustfmt::skip]
+fn let_if_cond() -> (RuleSource, Rewrite<Lambda, LambdaAnalysis>) {
+    rule!("let-if-cond";
+        "(let ?v ?e (if ?cond ?then ?else))" =>
+        "(let ?v ?e (if (let ?v ?e ?cond) ?then ?else))"
+        if is_only_free_in(var("?v"), var("?cond"), var("?e")))
+}
+
+/// Replaces `fix` in `rules` (if it's there) with `fix-app`, which only
+/// unrolls a fixpoint one level where it's applied. `fix` unrolls every
+/// fixpoint every iteration whether or not anything calls it, so this can
+/// converge in fewer iterations, but a fixpoint that's never applied (or is
+/// only passed around) is never unrolled at all.
+pub fn lazy_fix(
+    rules: Vec<Rewrite<Lambda, LambdaAnalysis>>,
+) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    let len = rules.len();
+    let mut rules: Vec<_> = rules.into_iter().filter(|r| r.name() != "fix").collect();
+    if rules.len() < len {
+        rules.push(fix_app().1);
+    }
+    rules
+}
+
+#[rustfmt::skip]
+fn fix_app() -> (RuleSource, Rewrite<Lambda, LambdaAnalysis>) {
+    rule!("fix-app";
+        "(app (fix ?v ?body) ?arg)" => "(app (let ?v (fix ?v ?body) ?body) ?arg)")
+}
+
+/// Replaces `fix` in `rules` (if it's there) with `fix-bounded`, which
+/// unrolls each fixpoint at most `max_depth` times, setting the analysis'
+/// `fix_depth_hit` when it declines to go deeper. An e-graph doesn't record
+/// how a term was derived, and `fix` puts the unrolling in the fixpoint's
+/// own e-class, so one unrolling is really all of them. Instead, this tags
+/// depth in the fixpoint's variable: `(fix v e)` unrolls to
+/// `(let v (fix v@1 (let v (var v@1) e)) e)`, a renamed fixpoint one deeper,
+/// which unrolls in turn until `v@max_depth`. So depth counts unrollings
+/// along one chain, and programs shouldn't name their variables with `@`.
+pub fn bounded_fix(
+    rules: Vec<Rewrite<Lambda, LambdaAnalysis>>,
+    max_depth: usize,
+) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    let len = rules.len();
+    let mut rules: Vec<_> = rules.into_iter().filter(|r| r.name() != "fix").collect();
+    if rules.len() < len {
+        rules.push(fix_bounded(max_depth).1);
+    }
+    rules
+}
+
+#[rustfmt::skip]
+fn fix_bounded(max_depth: usize) -> (RuleSource, Rewrite<Lambda, LambdaAnalysis>) {
+    rule!("fix-bounded"; "(fix ?v ?body)" =>
+        { BoundedFix { v: var("?v"), body: var("?body"), max_depth } })
+}
+
+/// The name `v` of a `bounded_fix` variable `v@depth`, and the depth.
+fn unroll_depth(name: &str) -> (&str, usize) {
+    match name.rsplit_once('@') {
+        Some((base, depth)) => match depth.parse() {
+            Ok(depth) => (base, depth),
+            Err(_) => (name, 0),
+        },
+        None => (name, 0),
+    }
+}
+
+/// Adds `(let v (fix v@n+1 (let v (var v@n+1) body)) body)` for `(fix v@n
+/// body)`, up to `max_depth`; see `bounded_fix`.
+struct BoundedFix {
+    v: Var,
+    body: Var,
+    max_depth: usize,
+}
+
+impl Applier<Lambda, LambdaAnalysis> for BoundedFix {
+    fn apply_one(&self, egraph: &mut EGraph, _eclass: Id, subst: &Subst) -> Vec<Id> {
+        let (v, body) = (subst[self.v], subst[self.body]);
+        let name = match egraph[v].nodes.iter().find_map(|node| match node {
+            Lambda::Symbol(name) => Some(name.to_string()),
+            _ => None,
+        }) {
+            Some(name) => name,
+            None => return vec![],
+        };
+        let (base, depth) = unroll_depth(&name);
+        if depth >= self.max_depth {
+            egraph.analysis.fix_depth_hit = true;
+            return vec![];
+        }
+        let inner = egraph.add(Lambda::Symbol(format!("{}@{}", base, depth + 1).into()));
+        let inner_var = egraph.add(Lambda::Var(inner));
+        let renamed = egraph.add(Lambda::Let([v, inner_var, body]));
+        let inner_fix = egraph.add(Lambda::Fix([inner, renamed]));
+        vec![egraph.add(Lambda::Let([v, inner_fix, body]))]
+    }
+}
+
+/// Replaces `add-comm` and `add-assoc` in `rules` (if they're there) with
+/// `add-flatten`, which unions each binary `+` with a `+*` of all the
+/// operands of the sums under it, in e-class id order, with the constant
+/// operands folded into one. A folded `0` is kept, as dropping it is
+/// `add-zero`'s job. A sum of `n` terms then gets one `+*` rather
+/// than the exponentially many trees AC rewriting finds. Only binary sums
+/// are flattened, and an operand's e-class contributes the operands of its
+/// longest `+*`, so this doesn't find every flattening either.
+pub fn nary_add(
+    rules: Vec<Rewrite<Lambda, LambdaAnalysis>>,
+) -> Vec<Rewrite<Lambda, LambdaAnalysis>> {
+    let len = rules.len();
+    let mut rules: Vec<_> = rules
+        .into_iter()
+        .filter(|r| r.name() != "add-comm" && r.name() != "add-assoc")
+        .collect();
+    if rules.len() < len {
+        rules.push(add_flatten().1);
+    }
+    rules
+}
+
+#[rustfmt::skip]
+fn add_flatten() -> (RuleSource, Rewrite<Lambda, LambdaAnalysis>) {
+    rule!("add-flatten"; "(+ ?a ?b)" => { FlattenAdd { a: var("?a"), b: var("?b") } })
+}
+
+struct FlattenAdd {
+    a: Var,
+    b: Var,
+}
+
+/// The operands of the longest `+*` in `id`'s e-class, or just `id`.
+fn add_operands(egraph: &EGraph, id: Id) -> Vec<Id> {
+    let longest = egraph[id]
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            Lambda::AddN(operands) => Some(operands),
+            _ => None,
+        })
+        .max_by_key(|operands| operands.len());
+    match longest {
+        Some(operands) => operands.to_vec(),
+        None => vec![id],
+    }
+}
+
+impl Applier<Lambda, LambdaAnalysis> for FlattenAdd {
+    fn apply_one(&self, egraph: &mut EGraph, _eclass: Id, subst: &Subst) -> Vec<Id> {
+        let mut operands = add_operands(egraph, subst[self.a]);
+        operands.extend(add_operands(egraph, subst[self.b]));
+        let mut sum: Option<i64> = None;
+        let mut rest = vec![];
+        for id in operands {
+            let n = egraph[id].data.constant.as_ref().and_then(|c| c.num());
+            let folds = egraph.analysis.fold_ops.contains("+");
+            match n
+                .filter(|_| folds)
+                .and_then(|n| sum.unwrap_or(0).checked_add(n))
+            {
+                Some(total) => sum = Some(total),
+                None => rest.push(egraph.find(id)),
+            }
+        }
+        if let Some(n) = sum {
+            rest.push(egraph.add(Lambda::Num(n)));
+        }
+        rest.sort();
+        if rest.len() == 1 {
+            return rest;
+        }
+        vec![egraph.add(Lambda::AddN(rest.into()))]
+    }
+}
+
 struct CaptureAvoid {
     fresh: Var,
     v2: Var,
@@ -205,6 +1837,14 @@ impl Applier<Lambda, LambdaAnalysis> for CaptureAvoid {
         if v2_free_in_e {
             let mut subst = subst.clone();
             let sym = Lambda::Symbol(format!("_{}", eclass).into());
+            if egraph.lookup(sym.clone()).is_none() {
+                let analysis = &mut egraph.analysis;
+                if analysis.fresh_var_limit == Some(analysis.fresh_vars) {
+                    analysis.fresh_var_limit_hit = true;
+                    return vec![];
+                }
+                analysis.fresh_vars += 1;
+            }
             subst.insert(self.fresh, egraph.add(sym));
             self.if_free.apply_one(egraph, eclass, &subst)
         } else {
@@ -212,3 +1852,678 @@ impl Applier<Lambda, LambdaAnalysis> for CaptureAvoid {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Without `+` in `fold_ops`, `(+ 1 2)` doesn't fold while `=` still
+    /// does.
+    #[test]
+    fn fold_ops() {
+        let fold = |analysis: LambdaAnalysis, expr: &str| {
+            let mut egraph = EGraph::new(analysis);
+            let root = egraph.add_expr(&expr.parse().unwrap());
+            egraph.rebuild();
+            egraph[root].data.constant.clone()
+        };
+        let eq_only = || LambdaAnalysis::with_fold_ops(&["="]);
+        assert_eq!(fold(eq_only(), "(+ 1 2)"), None);
+        assert_eq!(fold(eq_only(), "(= 1 1)"), Some(Lambda::Bool(true)));
+        assert_eq!(fold(Default::default(), "(+ 1 2)"), Some(Lambda::Num(3)));
+    }
+
+    /// Sums past `i32` fold now numerals are `i64`, and sums past `i64` are
+    /// left alone rather than wrapping.
+    #[test]
+    fn wide_numerals() {
+        let fold = |expr: &str| {
+            let mut egraph = EGraph::new(LambdaAnalysis::default());
+            let root = egraph.add_expr(&expr.parse().unwrap());
+            egraph.rebuild();
+            egraph[root].data.constant.clone()
+        };
+        assert_eq!(
+            fold("(+ 2147483647 1)"),
+            Some(Lambda::Num(i64::from(i32::MAX) + 1))
+        );
+        assert_eq!(fold("(+ 9223372036854775807 1)"), None);
+    }
+
+    /// Unioning `(fix f body)` with its unrolling keeps the fixpoint's free
+    /// variables, just `x` here, and doesn't add back `f`.
+    #[test]
+    fn fix_class_free_variables() {
+        let body = "(app (var f) (var x))";
+        let mut egraph = EGraph::new(LambdaAnalysis::default());
+        let fix = format!("(fix f {})", body);
+        let unrolled = format!("(let f {} {})", fix, body);
+        let fix = egraph.add_expr(&fix.parse().unwrap());
+        let unrolled = egraph.add_expr(&unrolled.parse().unwrap());
+        egraph.union(fix, unrolled);
+        egraph.rebuild();
+        let x = egraph.lookup(Lambda::Symbol("x".into())).unwrap();
+        let expected: HashSet<Id> = std::iter::once(x).collect();
+        assert_eq!(egraph[fix].data.free, expected);
+    }
+
+    /// The JSON export has every e-class once, in id order, with its e-nodes
+    /// and folded constant.
+    #[test]
+    fn export_json() {
+        let mut egraph = EGraph::new(LambdaAnalysis::default());
+        let root = egraph.add_expr(&"(+ 1 2)".parse().unwrap());
+        egraph.rebuild();
+        let classes: Vec<JsonEClass> = serde_json::from_str(&export_egraph_json(&egraph)).unwrap();
+        assert_eq!(classes.len(), egraph.number_of_classes());
+        assert!(classes.windows(2).all(|w| w[0].id < w[1].id));
+        let nodes: usize = classes.iter().map(|class| class.nodes.len()).sum();
+        assert_eq!(nodes, egraph.total_number_of_nodes());
+        let root = classes
+            .iter()
+            .find(|class| class.id == usize::from(egraph.find(root)))
+            .unwrap();
+        assert_eq!(root.constant.as_deref(), Some("3"));
+        let mut ops: Vec<&str> = root.nodes.iter().map(|node| node.op.as_str()).collect();
+        ops.sort();
+        assert_eq!(ops, ["+", "3"]);
+    }
+
+    /// `programs/fib.lam` loads with its comments blanked out, in place, as
+    /// the same program as `lambda_bench2`.
+    #[test]
+    fn program_file_with_comments() {
+        let src = "(+ 1 ; one\n 2)";
+        assert_eq!(strip_comments(src), "(+ 1      \n 2)");
+        let bench = lambda_bench_file("programs/fib.lam").unwrap();
+        assert_eq!(bench.name, "fib");
+        let expr: RecExpr<Lambda> = bench.start_exprs[0].parse().unwrap();
+        assert_eq!(expr, FIB.parse::<RecExpr<Lambda>>().unwrap());
+    }
+
+    /// Operator names are fine as variables: `(var app)` is a variable named
+    /// `app`, not an application, and prints back the same.
+    #[test]
+    fn operator_names_as_variables() {
+        for name in &["app", "let"] {
+            let src = format!("(var {})", name);
+            let expr: RecExpr<Lambda> = src.parse().unwrap();
+            match expr.as_ref() {
+                [Lambda::Symbol(s), Lambda::Var(v)] => {
+                    assert_eq!(s.to_string(), *name);
+                    assert_eq!(usize::from(*v), 0);
+                }
+                nodes => panic!("{} parsed as {:?}", src, nodes),
+            }
+            assert_eq!(expr.to_string(), src);
+            program_bench("binders", &src).check_binders().unwrap();
+        }
+    }
+
+    /// `check_binders` rejects variables that parse as literals, and
+    /// programs that don't parse, without panicking.
+    #[test]
+    fn binders_parse_as_literals() {
+        assert!(program_bench("binders", "(lam 1 (var 1))")
+            .check_binders()
+            .is_err());
+        assert!(program_bench("binders", "(var").check_binders().is_err());
+    }
+
+    /// Eager and lazy constant union fold `lambda_bench_constfold(6)` to the
+    /// same constant.
+    #[test]
+    fn const_union() {
+        let bench = lambda_bench_constfold(6);
+        let comparison = compare_const_union(&bench, &RunConfig::default()).unwrap();
+        assert!(comparison.same_constants, "{:#?}", comparison);
+    }
+
+    /// `LambdaAnalysis` extracts a smaller `lambda_bench_constfold(3)` than
+    /// `UnitAnalysis` under the same plain arithmetic rules, since only it
+    /// folds constants.
+    #[test]
+    fn compare_analyses_folds() {
+        let expr = lambda_bench_constfold(3).start_exprs.remove(0);
+        let comparison = compare_analyses(
+            &expr,
+            PLAIN_ARITH_RULES,
+            LambdaAnalysis::default(),
+            UnitAnalysis,
+            &RunConfig::default(),
+        )
+        .unwrap();
+        assert!(
+            comparison.a.best_cost < comparison.b.best_cost,
+            "LambdaAnalysis extracted cost {}, no smaller than {} without an analysis",
+            comparison.a.best_cost,
+            comparison.b.best_cost
+        );
+    }
+
+    /// `LambdaAnalysis::merge` against egg's contract (see the comment on
+    /// it): absorbing a constant or shrinking the free set changes `to` and
+    /// returns `None`, and otherwise `to` is left alone and it returns
+    /// `Some(Greater)`.
+    #[test]
+    fn merge_contract() {
+        let data = |free: &[usize], constant: Option<i64>| Data {
+            free: free.iter().map(|&i| Id::from(i)).collect(),
+            constant: constant.map(Lambda::Num),
+        };
+        let cases = [
+            (
+                "constant-absorb",
+                data(&[], None),
+                data(&[], Some(3)),
+                None,
+                data(&[], Some(3)),
+            ),
+            (
+                "free-set-shrink",
+                data(&[1, 2], None),
+                data(&[1], None),
+                None,
+                data(&[1], None),
+            ),
+            (
+                "absorb-and-shrink",
+                data(&[1, 2], None),
+                data(&[1], Some(3)),
+                None,
+                data(&[1], Some(3)),
+            ),
+            (
+                "no-change",
+                data(&[1], Some(3)),
+                data(&[1, 2], Some(3)),
+                Some(Ordering::Greater),
+                data(&[1], Some(3)),
+            ),
+            (
+                "no-change-keeps-own-constant",
+                data(&[], Some(3)),
+                data(&[], None),
+                Some(Ordering::Greater),
+                data(&[], Some(3)),
+            ),
+        ];
+        let analysis = LambdaAnalysis::default();
+        for (name, to, from, expected, merged) in cases.iter().cloned() {
+            let mut to = to;
+            assert_eq!(analysis.merge(&mut to, from), expected, "{}", name);
+            assert_eq!(to.free, merged.free, "{}", name);
+            assert_eq!(to.constant, merged.constant, "{}", name);
+        }
+    }
+
+    /// Negative literals parse and print back as themselves, rather than as
+    /// a symbol or some subtraction, and fold in sums.
+    #[test]
+    fn negative_literals() {
+        for literal in &["-1", "-2", "(+ (var n) -1)"] {
+            let expr: RecExpr<Lambda> = literal.parse().unwrap();
+            assert_eq!(expr.to_string(), *literal);
+        }
+        let expr: RecExpr<Lambda> = "-1".parse().unwrap();
+        assert_eq!(expr.as_ref(), &[Lambda::Num(-1)]);
+        check_equiv("(+ 5 -2)", &["3"], &rules(), 30).unwrap();
+    }
+
+    /// A golden file written for `lambda_bench2` matches a second run.
+    #[test]
+    fn golden_rerun() {
+        let path = std::env::temp_dir().join(format!(
+            "egg-bench-golden-rerun-{}.expected",
+            std::process::id()
+        ));
+        let bench = lambda_bench2();
+        let config = RunConfig::default();
+        assert_extracted_matches_golden(&bench, &config, &path, true);
+        assert_extracted_matches_golden(&bench, &config, &path, false);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Malformed programs get errors naming the operator and where it is,
+    /// rather than panics.
+    #[test]
+    fn arity_errors() {
+        for (src, expected) in &[("(+ 1)", "`+` at 1:1"), ("(if true 1)", "`if` at 1:1")] {
+            let e = parse_program(src).unwrap_err();
+            assert!(e.to_string().contains(expected), "{}: {}", src, e);
+        }
+    }
+
+    /// `bounded_fix` at depth 2 stops `lambda_bench2` (fib 4, which recurses
+    /// deeper than that) from evaluating all the way to 3.
+    #[test]
+    fn fix_depth_bounds_fib() {
+        let bench = lambda_bench2().with_rules(bounded_fix(rules(), 2));
+        let (runner, _) = bench.saturate(&RunConfig::default()).unwrap();
+        assert!(runner.egraph.analysis.fix_depth_hit);
+        assert_eq!(runner.egraph[runner.roots[0]].data.constant, None);
+    }
+
+    /// `rules_for` picks out rules by tag, and every rule has one of them.
+    #[test]
+    fn rules_for_tags() {
+        let names = |rules: Vec<Rewrite<Lambda, LambdaAnalysis>>| -> Vec<String> {
+            rules.iter().map(|rule| rule.name().to_string()).collect()
+        };
+        assert_eq!(
+            names(rules_for(&[RuleTag::Arith])),
+            ["add-comm", "add-assoc", "eq-comm", "eq-same", "add-zero"]
+        );
+        let all = [RuleTag::Arith, RuleTag::Subst, RuleTag::Control];
+        assert_eq!(names(rules_for(&all)), names(rules()));
+    }
+
+    #[test]
+    fn rule_meta_matches_rules() {
+        if let Err(failures) = check_rule_meta() {
+            panic!("{}", failures.join("\n"));
+        }
+    }
+
+    /// `bidirectional` keeps each rule and adds the reverse of the
+    /// reversible ones, all of which `tagged_rules` has patterns for.
+    #[test]
+    fn bidirectional_adds_reverses() {
+        let rules = bidirectional(rules());
+        let names: Vec<String> = rules.iter().map(|rule| rule.name().to_string()).collect();
+        assert!(names.contains(&"let-add".to_string()));
+        assert!(names.contains(&"let-add-rev".to_string()));
+        assert!(!names.contains(&"add-comm-rev".to_string()));
+        assert_eq!(reversible_sources().len(), REVERSIBLE_RULES.len());
+        let lhs = |name: &str| rule_lhs().into_iter().find(|(n, _)| n == name).unwrap().1;
+        assert_eq!(lhs("app-if-rev"), "(if ?c (app ?f ?x) (app ?g ?x))");
+    }
+
+    /// Each name picks out the bench of that name, or a sized one named
+    /// after it, like `sum10000`.
+    #[test]
+    fn bench_by_name_names() {
+        let names = [
+            "lambda",
+            "lambda0",
+            "lambda1",
+            "lambda2",
+            "letrec",
+            "onestep",
+            "random",
+            "sum",
+            "manyvars",
+            "constfold",
+            "wide",
+        ];
+        for name in names.iter() {
+            let bench = bench_by_name(name).unwrap();
+            assert!(bench.name.starts_with(name), "{} is {}", name, bench.name);
+        }
+        assert_eq!(bench_by_name("lambda2").unwrap().name, "lambda2");
+        assert!(matches!(bench_by_name("nope"), Err(Error::UnknownBench(_))));
+    }
+
+    /// `canonical_ac` saturates a sum of five variables and the same sum
+    /// reversed with far fewer e-nodes than naive AC, and still proves them
+    /// equal.
+    #[test]
+    fn canonical_ac_five_operands() {
+        let sum = "(+ (var a) (+ (var b) (+ (var c) (+ (var d) (var e)))))";
+        let reversed = "(+ (+ (+ (+ (var e) (var d)) (var c)) (var b)) (var a))";
+        let run = |rules: Vec<Rewrite<Lambda, LambdaAnalysis>>| {
+            let runner: Runner<Lambda, LambdaAnalysis> = Runner::default()
+                .with_expr(&sum.parse().unwrap())
+                .with_expr(&reversed.parse().unwrap())
+                .run(&rules);
+            assert!(matches!(runner.stop_reason, Some(StopReason::Saturated)));
+            let (a, b) = (runner.roots[0], runner.roots[1]);
+            assert_eq!(runner.egraph.find(a), runner.egraph.find(b));
+            runner.egraph.total_number_of_nodes()
+        };
+        let naive = run(rules_for(&[RuleTag::Arith]));
+        let canonical = run(canonical_ac(rules_for(&[RuleTag::Arith])));
+        assert!(
+            canonical * 2 < naive,
+            "{} e-nodes with canonical AC, {} with naive AC",
+            canonical,
+            naive
+        );
+    }
+
+    /// A condition doubling `v` ten times is a DAG of a dozen e-classes but
+    /// a tree of thousands of terms, and `eval_under_let` evaluates each
+    /// e-class once.
+    #[test]
+    fn eval_under_let_memoizes() {
+        let mut egraph = EGraph::default();
+        let mut term = "(var v)".to_string();
+        for _ in 0..10 {
+            term = format!("(+ {} {})", term, term);
+        }
+        let cond = egraph.add_expr(&format!("(= {} 1024)", term).parse().unwrap());
+        egraph.rebuild();
+        let v = egraph.lookup(Lambda::Symbol("v".into())).unwrap();
+        let mut memo = HashMap::new();
+        let result = eval_under_let(&egraph, v, &Lambda::Num(1), cond, LET_COND_FUEL, &mut memo);
+        assert_eq!(result, Some(Lambda::Bool(true)));
+        // the `=`, the ten sums, and `(var v)`
+        assert_eq!(memo.len(), 12);
+    }
+
+    /// `if-eq-then` rebinds `x` once when the other side of the test doesn't
+    /// mention `x`, and leaves it alone when it does, rather than wrapping
+    /// the branch in lets forever.
+    #[test]
+    fn if_eq_then_terminates() {
+        let only = filter_rules(&rules(), Some(&["if-eq-then".into()]), &[]).unwrap();
+        for expr in &[
+            "(lam x (if (= (var x) 1) (+ (var x) 1) 0))",
+            "(lam x (if (= (var x) (+ (var x) 1)) (var x) 0))",
+        ] {
+            let runner: Runner<Lambda, LambdaAnalysis> = Runner::default()
+                .with_iter_limit(10)
+                .with_expr(&expr.parse().unwrap())
+                .run(&only);
+            assert!(
+                matches!(runner.stop_reason, Some(StopReason::Saturated)),
+                "{}: {:?}",
+                expr,
+                runner.stop_reason
+            );
+        }
+    }
+
+    /// A saturated e-graph saved and loaded again has the same e-classes,
+    /// e-nodes, and analysis data.
+    #[test]
+    fn save_load_roundtrip() {
+        let (runner, _) = lambda_bench0().saturate(&RunConfig::default()).unwrap();
+        let egraph = &runner.egraph;
+        let path = std::env::temp_dir().join(format!("egraph-{}.json", std::process::id()));
+        save_egraph(egraph, &path).unwrap();
+        let loaded = load_egraph(&path, egraph.analysis.clone());
+        let _ = std::fs::remove_file(&path);
+        let (loaded, ids) = loaded.unwrap();
+        assert_eq!(loaded.number_of_classes(), egraph.number_of_classes());
+        assert_eq!(
+            loaded.total_number_of_nodes(),
+            egraph.total_number_of_nodes()
+        );
+        for class in egraph.classes() {
+            let new = &loaded[ids[&usize::from(class.id)]];
+            assert_eq!(new.nodes.len(), class.nodes.len());
+            assert_eq!(new.data.constant, class.data.constant);
+            assert_eq!(new.data.free.len(), class.data.free.len());
+        }
+    }
+
+    /// `letrec` desugars to the `let` and `fix` of `lambda_bench2`, so the
+    /// two get the same result.
+    #[test]
+    fn letrec_matches_fix() {
+        let config = RunConfig::default();
+        let letrec = lambda_bench_letrec().run(&config).unwrap();
+        let fix = lambda_bench2().run(&config).unwrap();
+        assert_eq!(letrec.results, fix.results);
+    }
+
+    /// Both groupings of `a + b + c` flatten to a three operand `+*`, in one
+    /// e-class, with fewer e-nodes than AC rewriting makes.
+    #[test]
+    fn nary_add_flattens() {
+        let right = "(+ (var a) (+ (var b) (var c)))";
+        let left = "(+ (+ (var a) (var b)) (var c))";
+        let run = |rules: Vec<Rewrite<Lambda, LambdaAnalysis>>| {
+            let runner: Runner<Lambda, LambdaAnalysis> = Runner::default()
+                .with_expr(&right.parse().unwrap())
+                .with_expr(&left.parse().unwrap())
+                .run(&rules);
+            assert!(matches!(runner.stop_reason, Some(StopReason::Saturated)));
+            let (a, b) = (runner.roots[0], runner.roots[1]);
+            assert_eq!(runner.egraph.find(a), runner.egraph.find(b));
+            runner
+        };
+        let ac = run(rules_for(&[RuleTag::Arith]));
+        let nary = run(nary_add(rules_for(&[RuleTag::Arith])));
+        let flat = nary.egraph[nary.roots[0]]
+            .nodes
+            .iter()
+            .any(|node| matches!(node, Lambda::AddN(operands) if operands.len() == 3));
+        assert!(flat, "no three operand +* for {}", right);
+        assert!(
+            nary.egraph.total_number_of_nodes() < ac.egraph.total_number_of_nodes(),
+            "{} e-nodes flattened, {} with AC",
+            nary.egraph.total_number_of_nodes(),
+            ac.egraph.total_number_of_nodes()
+        );
+    }
+
+    /// Flattening keeps a zero operand, so `(+ x 0)` isn't `x` without
+    /// `add-zero`.
+    #[test]
+    fn nary_add_keeps_zero() {
+        let mut egraph = EGraph::default();
+        let x = egraph.add_expr(&"(var x)".parse().unwrap());
+        let sum = egraph.add_expr(&"(+ (var x) 0)".parse().unwrap());
+        let runner = Runner::default()
+            .with_egraph(egraph)
+            .run(&[add_flatten().1]);
+        assert_ne!(runner.egraph.find(x), runner.egraph.find(sum));
+    }
+
+    /// `lambda_bench_sum` records how long parsing its 10,000 ones took, and
+    /// folds them to 10000 without any rules.
+    #[test]
+    fn large_sum_parse_time() {
+        let report = lambda_bench_sum(10_000)
+            .with_rules(vec![])
+            .run(&RunConfig::default())
+            .unwrap();
+        assert!(report.parse_time > 0.0);
+        assert_eq!(report.results, ["10000"]);
+    }
+
+    /// `PreferConstants` extracts `(+ 1 2)` as the literal `3`, at no cost,
+    /// but has no literal to pick for `(+ (var x) 0)`.
+    #[test]
+    fn prefer_constants() {
+        let mut egraph = EGraph::default();
+        let symbolic = egraph.add_expr(&"(+ (var x) 0)".parse().unwrap());
+        let constant = egraph.add_expr(&"(+ 1 2)".parse().unwrap());
+        egraph.rebuild();
+        let extractor = Extractor::new(&egraph, PreferConstants);
+        let (cost, best) = extractor.find_best(constant);
+        assert_eq!((cost, best.to_string()), (0, "3".to_string()));
+        let (cost, best) = extractor.find_best(symbolic);
+        assert!(cost > 0);
+        assert_eq!(best.to_string(), "(+ (var x) 0)");
+    }
+
+    /// `check_closed` rejects a program with a free variable, naming it, but
+    /// accepts the closed `lambda_bench2`.
+    #[test]
+    fn check_closed_names_free_variable() {
+        let err = program_bench("open", "(var undefined)")
+            .check_closed()
+            .unwrap_err();
+        assert!(err.contains("undefined"), "{}", err);
+        assert_eq!(lambda_bench2().check_closed(), Ok(()));
+    }
+
+    /// Pushing `let y` under `lam x` when `y` is bound to `(var x)` needs a
+    /// fresh name. With no fresh variables allowed, capture avoidance declines
+    /// instead, and the run still finishes without any of its names.
+    #[test]
+    fn fresh_var_limit() {
+        let run = |fresh_var_limit| {
+            let analysis = LambdaAnalysis {
+                fresh_var_limit,
+                ..Default::default()
+            };
+            Runner::new(analysis)
+                .with_expr(
+                    &"(let y (var x) (lam x (+ (var x) (var y))))"
+                        .parse()
+                        .unwrap(),
+                )
+                .run(&rules())
+        };
+        assert!(run(None).egraph.analysis.fresh_vars > 0);
+
+        let runner = run(Some(0));
+        assert!(runner.egraph.analysis.fresh_var_limit_hit);
+        assert_eq!(runner.egraph.analysis.fresh_vars, 0);
+        let fresh = runner
+            .egraph
+            .classes()
+            .flat_map(|class| &class.nodes)
+            .any(|node| matches!(node, Lambda::Symbol(s) if s.to_string().starts_with('_')));
+        assert!(!fresh);
+    }
+
+    /// `add-zero` simplifies `(+ (var x) 0)` to `(var x)`, which constant
+    /// folding can't, as `x` is symbolic.
+    #[test]
+    fn add_zero_symbolic() {
+        let mut egraph = EGraph::default();
+        let x = egraph.add_expr(&"(var x)".parse().unwrap());
+        let sum = egraph.add_expr(&"(+ (var x) 0)".parse().unwrap());
+        let runner = Runner::default().with_egraph(egraph).run(&rules());
+        assert_eq!(runner.egraph.find(x), runner.egraph.find(sum));
+        let (_, best) = Extractor::new(&runner.egraph, AstSize).find_best(sum);
+        assert_eq!(best.to_string(), "(var x)");
+    }
+
+    /// The sum of `lambda_bench_manyvars(10)` has all ten variables free.
+    #[test]
+    fn manyvars_free() {
+        let bench = lambda_bench_manyvars(10);
+        let mut egraph = EGraph::new(bench.analysis.clone());
+        let root = egraph.add_expr(&bench.start_exprs[0].parse().unwrap());
+        egraph.rebuild();
+        assert_eq!(egraph[root].data.free.len(), 10);
+    }
+
+    /// `eq-same` folds `(= (var x) (var x))` to `true` though `x` is symbolic.
+    #[test]
+    fn eq_same_symbolic() {
+        let mut egraph = EGraph::default();
+        let eq = egraph.add_expr(&"(= (var x) (var x))".parse().unwrap());
+        let runner = Runner::default().with_egraph(egraph).run(&rules());
+        let (_, best) = Extractor::new(&runner.egraph, AstSize).find_best(eq);
+        assert_eq!(best.to_string(), "true");
+    }
+
+    /// With `lazy_let_if`, `lambda_bench0` and `lambda_bench2` still simplify
+    /// to what they do with `let-if`, as their conditions all fold.
+    #[test]
+    fn lazy_let_if_parity() {
+        let config = RunConfig::default();
+        for bench in vec![lambda_bench0(), lambda_bench2()] {
+            let lazy = bench.clone().with_rules(lazy_let_if(rules()));
+            assert!(lazy.rules.iter().any(|r| r.name() == "let-if-cond"));
+            assert!(lazy.rules.iter().all(|r| r.name() != "let-if"));
+            let full = bench.run(&config).unwrap();
+            assert_eq!(lazy.run(&config).unwrap().results, full.results);
+        }
+    }
+
+    /// Weighting `lam` heavily makes extraction pass over the smallest term,
+    /// which has a `lam`, for a larger one without any.
+    #[test]
+    fn weighted_cost_avoids_lam() {
+        let mut egraph = EGraph::default();
+        let lam = egraph.add_expr(&"(lam x (var y))".parse().unwrap());
+        let sum = egraph.add_expr(&"(+ (+ (var y) (var y)) (var y))".parse().unwrap());
+        egraph.union(lam, sum);
+        egraph.rebuild();
+        let (_, best) = Extractor::new(&egraph, AstSize).find_best(lam);
+        assert_eq!(best.to_string(), "(lam x (var y))");
+        let weights: WeightedCost = "lam=100".parse().unwrap();
+        let (cost, best) = Extractor::new(&egraph, weights).find_best(lam);
+        assert_eq!(best.to_string(), "(+ (+ (var y) (var y)) (var y))");
+        assert_eq!(cost, 8);
+    }
+
+    /// Saturating `lambda_bench2` folds fib 4 to the constant 3.
+    #[test]
+    fn lambda2_result_constant() {
+        let (runner, _) = lambda_bench2().saturate(&RunConfig::default()).unwrap();
+        assert_result_constant(&runner.egraph, runner.roots[0], &Lambda::Num(3));
+    }
+
+    /// Every case in `corpus.txt` holds under `rules()`.
+    #[test]
+    fn corpus() {
+        if let Err(failures) = check_corpus("corpus.txt") {
+            panic!(
+                "{} corpus cases failed:\n{}",
+                failures.len(),
+                failures.join("\n")
+            );
+        }
+    }
+
+    /// `lambda_bench_onestep` extracts `1` within two iterations.
+    #[test]
+    fn onestep() {
+        let report = lambda_bench_onestep().run(&RunConfig::default()).unwrap();
+        check_onestep(&report).unwrap();
+    }
+
+    /// The operator counts of saturated `lambda_bench2` account for every
+    /// e-node.
+    #[test]
+    fn operator_counts_sum_to_nodes() {
+        let (runner, report) = lambda_bench2().saturate(&RunConfig::default()).unwrap();
+        let counts = operator_counts(&runner.egraph);
+        check_operator_counts(&counts, report.nodes).unwrap();
+    }
+
+    /// Counting calls while saturating `lambda_bench2` sees a `make` for every
+    /// e-node, and some merges and modifies.
+    #[test]
+    fn analysis_stats_cover_nodes() {
+        let bench = Bench {
+            analysis: LambdaAnalysis {
+                count_calls: true,
+                ..Default::default()
+            },
+            ..lambda_bench2()
+        };
+        reset_analysis_stats();
+        let report = bench.run(&RunConfig::default()).unwrap();
+        check_analysis_stats(&analysis_stats(), report.nodes).unwrap();
+    }
+
+    /// `lambda_bench_constfold(4)` has 16 leaves alternating 0 and 1, so it
+    /// folds to 8.
+    #[test]
+    fn constfold_sum() {
+        let (runner, report) = lambda_bench_constfold(4)
+            .saturate(&RunConfig::default())
+            .unwrap();
+        assert_result_constant(&runner.egraph, runner.roots[0], &Lambda::Num(8));
+        assert_eq!(report.results, ["8"]);
+    }
+
+    /// Every sum `lambda_bench_wide(5)` unions folds to 5, which is what it
+    /// extracts, and extracting it takes measurable time.
+    #[test]
+    fn wide_extracts_constant() {
+        let report = lambda_bench_wide(5).run(&RunConfig::default()).unwrap();
+        assert_eq!(report.results, ["5"]);
+        assert!(report.extraction_time > 0.0);
+    }
+
+    /// `app-if` applies the branch taken, so
+    /// `(app (if true (lam x (var x)) (lam x 0)) 5)` simplifies to `5`.
+    #[test]
+    fn app_if_simplifies() {
+        let report = program_bench("app-if", "(app (if true (lam x (var x)) (lam x 0)) 5)")
+            .run(&RunConfig::default())
+            .unwrap();
+        assert_eq!(report.results, ["5"]);
+    }
+}
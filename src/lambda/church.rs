@@ -0,0 +1,130 @@
+//! Church encodings of numerals and booleans.
+//!
+//! The decoders look for an encoding among the e-nodes of an e-class, so a
+//! term only has to be equivalent to one under the rules, not syntactically
+//! one. Binder names don't matter.
+use super::*;
+
+fn sym(expr: &mut RecExpr<Lambda>, s: &str) -> Id {
+    expr.add(Lambda::Symbol(s.into()))
+}
+
+/// `(lam f (lam x (app (var f) ... (app (var f) (var x)))))` with `n` apps.
+pub fn church_num(n: u32) -> RecExpr<Lambda> {
+    let mut expr = RecExpr::default();
+    let f = sym(&mut expr, "f");
+    let x = sym(&mut expr, "x");
+    let mut body = expr.add(Lambda::Var(x));
+    for _ in 0..n {
+        let f_var = expr.add(Lambda::Var(f));
+        body = expr.add(Lambda::App([f_var, body]));
+    }
+    let inner = expr.add(Lambda::Lambda([x, body]));
+    expr.add(Lambda::Lambda([f, inner]));
+    expr
+}
+
+/// `(lam t (lam f (var t)))` for true, `(lam t (lam f (var f)))` for false.
+pub fn church_bool(b: bool) -> RecExpr<Lambda> {
+    let mut expr = RecExpr::default();
+    let t = sym(&mut expr, "t");
+    let f = sym(&mut expr, "f");
+    let body = expr.add(Lambda::Var(if b { t } else { f }));
+    let inner = expr.add(Lambda::Lambda([f, body]));
+    expr.add(Lambda::Lambda([t, inner]));
+    expr
+}
+
+/// The `(binder, body)` pairs of the `lam` e-nodes in `id`.
+fn lams(egraph: &EGraph, id: Id) -> Vec<(Id, Id)> {
+    egraph[id]
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            Lambda::Lambda([v, body]) => Some((egraph.find(*v), egraph.find(*body))),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_var(egraph: &EGraph, id: Id, v: Id) -> bool {
+    egraph[id]
+        .nodes
+        .iter()
+        .any(|node| matches!(node, Lambda::Var(w) if egraph.find(*w) == v))
+}
+
+/// How many times `f` is applied on top of `x` in `id`, if that's all `id`
+/// is. `path` guards against cycles in the e-graph.
+fn count_apps(egraph: &EGraph, f: Id, x: Id, id: Id, path: &mut Vec<Id>) -> Option<u32> {
+    if is_var(egraph, id, x) {
+        return Some(0);
+    }
+    // with `(lam f (lam f ...))` the inner binder shadows `f`
+    if f == x || path.contains(&id) {
+        return None;
+    }
+    path.push(id);
+    let n = egraph[id].nodes.iter().find_map(|node| match node {
+        Lambda::App([g, arg]) if is_var(egraph, *g, f) => {
+            count_apps(egraph, f, x, egraph.find(*arg), path)
+        }
+        _ => None,
+    });
+    path.pop();
+    Some(n? + 1)
+}
+
+/// The numeral that e-class `id` is Church-encoded as, if any.
+pub fn decode_church_num(egraph: &EGraph, id: Id) -> Option<u32> {
+    let id = egraph.find(id);
+    lams(egraph, id).into_iter().find_map(|(f, body)| {
+        lams(egraph, body)
+            .into_iter()
+            .find_map(|(x, body)| count_apps(egraph, f, x, body, &mut vec![]))
+    })
+}
+
+/// The boolean that e-class `id` is Church-encoded as, if any.
+pub fn decode_church_bool(egraph: &EGraph, id: Id) -> Option<bool> {
+    let id = egraph.find(id);
+    lams(egraph, id).into_iter().find_map(|(t, body)| {
+        lams(egraph, body).into_iter().find_map(|(f, body)| {
+            if is_var(egraph, body, f) {
+                Some(false)
+            } else if is_var(egraph, body, t) {
+                Some(true)
+            } else {
+                None
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut egraph = EGraph::new(LambdaAnalysis::default());
+        for n in 0..5 {
+            let id = egraph.add_expr(&church_num(n));
+            assert_eq!(decode_church_num(&egraph, id), Some(n));
+        }
+        for &b in &[true, false] {
+            let id = egraph.add_expr(&church_bool(b));
+            assert_eq!(decode_church_bool(&egraph, id), Some(b));
+        }
+    }
+
+    /// A term only equivalent to an encoding under the rules decodes too.
+    #[test]
+    fn decodes_up_to_equivalence() {
+        let expr: RecExpr<Lambda> = format!("(app (lam n (var n)) {})", church_num(2))
+            .parse()
+            .unwrap();
+        let runner = Runner::default().with_expr(&expr).run(&rules());
+        assert_eq!(decode_church_num(&runner.egraph, runner.roots[0]), Some(2));
+    }
+}
@@ -0,0 +1,224 @@
+//! A compact text format for lambda benchmarks, so they can live in config
+//! files:
+//!
+//! ```text
+//! name: lambda2
+//! expr: (let fib (fix fib (lam n ...))
+//!     (app (var fib) 4))
+//! rules: default
+//! pats: auto
+//! ```
+//!
+//! Each line is `key: value`, and an indented line continues the value
+//! above it. `expr` and `pat` can be given more than once. `rules` is
+//! `default` for `rules()` or a comma separated list of their names, and
+//! `pats: auto` adds the left side of each rule as a pattern. Without
+//! `rules` a bench gets `rules()`, and without `pats` it has none. `;`
+//! starts a comment, as in program files.
+use super::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// The distinct left sides of `rules`, in rule order. Rules `rule_lhs`
+/// doesn't know, which none of `rules()` or its transforms are, are skipped.
+pub fn auto_patterns(rules: &[Rewrite<Lambda, LambdaAnalysis>]) -> Vec<String> {
+    let table = rule_lhs();
+    let mut pats: Vec<String> = vec![];
+    for rule in rules {
+        if let Some((_, lhs)) = table.iter().find(|(name, _)| name == rule.name()) {
+            if !pats.contains(lhs) {
+                pats.push(lhs.clone());
+            }
+        }
+    }
+    pats
+}
+
+/// `s` with each run of whitespace made one space, as the format keeps
+/// every value on one line.
+fn one_line(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn rule_names(rules: &[Rewrite<Lambda, LambdaAnalysis>]) -> Vec<String> {
+    rules.iter().map(|rule| rule.name().to_string()).collect()
+}
+
+/// Writes a bench in the format `FromStr` reads, with each program and
+/// pattern on one line. Tags, seeds, and assumptions aren't part of the format, so they're
+/// left out.
+impl fmt::Display for Bench<Lambda, LambdaAnalysis> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        for expr in &self.start_exprs {
+            writeln!(f, "expr: {}", one_line(expr))?;
+        }
+        let names = rule_names(&self.rules);
+        if names == rule_names(&rules()) {
+            writeln!(f, "rules: default")?;
+        } else {
+            writeln!(f, "rules: {}", names.join(", "))?;
+        }
+        let pats = self.bench_pats();
+        if !pats.is_empty() && pats == auto_patterns(&self.rules).as_slice() {
+            writeln!(f, "pats: auto")?;
+        } else {
+            for pat in pats {
+                writeln!(f, "pat: {}", one_line(pat))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Bench<Lambda, LambdaAnalysis> {
+    type Err = Error;
+
+    fn from_str(src: &str) -> Result<Self, Error> {
+        let src = strip_comments(src);
+        // (line number, key, value), with continuation lines joined on
+        let mut entries: Vec<(usize, &str, String)> = vec![];
+        for (i, line) in src.lines().enumerate() {
+            let n = i + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with(char::is_whitespace) {
+                match entries.last_mut() {
+                    Some((_, _, value)) => {
+                        value.push(' ');
+                        value.push_str(line.trim());
+                    }
+                    None => {
+                        return Err(Error::Parse(format!(
+                            "line {}: indented line with nothing to continue",
+                            n
+                        )))
+                    }
+                }
+                continue;
+            }
+            let colon = line
+                .find(':')
+                .ok_or_else(|| Error::Parse(format!("line {}: expected `key: value`", n)))?;
+            entries.push((
+                n,
+                line[..colon].trim(),
+                line[colon + 1..].trim().to_string(),
+            ));
+        }
+
+        let mut name = None;
+        let mut start_exprs = vec![];
+        let mut rules_line = None;
+        let mut auto_pats = false;
+        let mut bench_pats = vec![];
+        for (n, key, value) in entries {
+            let value = one_line(&value);
+            let err = |msg: String| Error::Parse(format!("line {}: {}", n, msg));
+            match key {
+                "name" if name.is_some() => return Err(err("name given twice".into())),
+                "name" => name = Some(value),
+                "expr" => {
+                    value
+                        .parse::<RecExpr<Lambda>>()
+                        .map_err(|e| err(format!("{}: {}", value, e)))?;
+                    start_exprs.push(value);
+                }
+                "rules" if rules_line.is_some() => return Err(err("rules given twice".into())),
+                "rules" => rules_line = Some((n, value)),
+                "pats" if value == "auto" => auto_pats = true,
+                "pats" => return Err(err(format!("pats is `auto`, not {}", value))),
+                "pat" => {
+                    value
+                        .parse::<Pattern<Lambda>>()
+                        .map_err(|e| err(format!("{}: {}", value, e)))?;
+                    bench_pats.push(value);
+                }
+                _ => return Err(err(format!("unknown key {}", key))),
+            }
+        }
+
+        let name = name.ok_or_else(|| Error::Parse("no name given".into()))?;
+        if start_exprs.is_empty() {
+            return Err(Error::Parse(format!("{} has no expr", name)));
+        }
+        let rules = match rules_line {
+            None => rules(),
+            Some((_, value)) if value == "default" => rules(),
+            Some((n, value)) => {
+                let only: Vec<String> = value.split(',').map(|s| s.trim().to_string()).collect();
+                filter_rules(&rules(), Some(&only), &[])
+                    .map_err(|e| Error::Parse(format!("line {}: {}", n, e)))?
+            }
+        };
+        if auto_pats {
+            bench_pats.extend(auto_patterns(&rules));
+        }
+        let bench = Bench {
+            name,
+            start_exprs,
+            rules,
+            tags: vec![],
+            ..lambda_bench()
+        };
+        Ok(bench.with_bench_pats(bench_pats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lambda_bench2`, and a variant with its patterns derived from a few
+    /// rules, come back the same through `to_string` and `parse`.
+    #[test]
+    fn roundtrip() {
+        let subset = filter_rules(&rules(), Some(&["beta".into(), "fix".into()]), &[]).unwrap();
+        let pats = auto_patterns(&subset);
+        let auto = lambda_bench2().with_rules(subset).with_bench_pats(pats);
+        for bench in [lambda_bench2(), auto].iter() {
+            let text = bench.to_string();
+            let back: Bench<Lambda, LambdaAnalysis> = text.parse().unwrap();
+            let exprs = |b: &Bench<Lambda, LambdaAnalysis>| -> Vec<RecExpr<Lambda>> {
+                b.start_exprs.iter().map(|e| e.parse().unwrap()).collect()
+            };
+            let pats: Vec<String> = bench.bench_pats().iter().map(|p| one_line(p)).collect();
+            assert_eq!(back.name, bench.name);
+            assert_eq!(exprs(&back), exprs(bench));
+            assert_eq!(rule_names(&back.rules), rule_names(&bench.rules));
+            assert_eq!(back.bench_pats(), pats.as_slice());
+            assert_eq!(back.to_string(), text);
+        }
+    }
+
+    /// A bad line is reported by number.
+    #[test]
+    fn bad_key() {
+        let src = "name: bad\nexpr: (+ 1 2)\nrulez: default\n";
+        match src.parse::<Bench<Lambda, LambdaAnalysis>>() {
+            Err(Error::Parse(msg)) => assert!(msg.starts_with("line 3:"), "{}", msg),
+            Err(e) => panic!("misreported a bad key: {}", e),
+            Ok(_) => panic!("parsed an unknown key"),
+        }
+    }
+
+    /// Every rule `rules()` and its transforms can make has a left side for
+    /// `pats: auto`, and each one parses as a pattern.
+    #[test]
+    fn auto_patterns_cover_transforms() {
+        let transformed =
+            bidirectional(bounded_fix(lazy_let_if(nary_add(canonical_ac(rules()))), 2));
+        let lazy = lazy_fix(rules());
+        let table = rule_lhs();
+        for rule in rules().iter().chain(&transformed).chain(&lazy) {
+            let lhs = table.iter().find(|(name, _)| name == rule.name());
+            assert!(lhs.is_some(), "no left side for {}", rule.name());
+        }
+        for (name, lhs) in &table {
+            lhs.parse::<Pattern<Lambda>>()
+                .unwrap_or_else(|e| panic!("{}: {}: {}", name, lhs, e));
+        }
+        assert!(auto_patterns(&transformed).contains(&"(fix ?v ?body)".to_string()));
+    }
+}
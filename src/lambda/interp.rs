@@ -0,0 +1,410 @@
+//! Two call-by-value interpreters for closed terms, written independently of
+//! each other and of the rules: `interp` substitutes values for variables,
+//! `interp_env` keeps them in an environment. Where the two disagree, one of
+//! them (or a rule checked against them) has a substitution bug.
+use super::*;
+use std::fmt;
+
+/// Steps either interpreter takes before giving up, since `fix` can loop.
+/// They recurse for every step, so this also bounds the stack.
+pub const FUEL: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Num(i64),
+    Bool(bool),
+    /// A function, with the environment it was made in. `interp` substitutes
+    /// instead, so its closures have an empty environment.
+    Closure {
+        param: String,
+        body: Term,
+        env: Env,
+    },
+    /// `(fix var expr)` in `env`, evaluated again each time `var` is looked
+    /// up. Only ever in an environment.
+    Rec {
+        var: String,
+        expr: Term,
+        env: Env,
+    },
+}
+
+pub type Env = HashMap<String, Value>;
+
+impl Value {
+    /// Whether the two values can't be told apart from outside: equal
+    /// literals, or both functions.
+    pub fn same_observable(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Closure { .. }, Value::Closure { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Closure { param, body, .. } => {
+                let lam = Term::Lambda(
+                    Box::new(Term::Symbol(param.clone())),
+                    Box::new(body.clone()),
+                );
+                write!(f, "{}", to_recexpr(&lam))
+            }
+            Value::Rec { var, .. } => write!(f, "<fix {}>", var),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpError {
+    Unbound(String),
+    /// An operator got an operand of the wrong kind, like `(+ true 1)`.
+    Type(String),
+    Overflow,
+    OutOfFuel,
+    /// A binder or variable that isn't a symbol.
+    Malformed(String),
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterpError::Unbound(v) => write!(f, "unbound variable {}", v),
+            InterpError::Type(msg) => write!(f, "type error: {}", msg),
+            InterpError::Overflow => write!(f, "integer overflow"),
+            InterpError::OutOfFuel => write!(f, "ran out of fuel after {} steps", FUEL),
+            InterpError::Malformed(msg) => write!(f, "malformed term: {}", msg),
+        }
+    }
+}
+
+fn symbol(term: &Term) -> Result<&str, InterpError> {
+    match term {
+        Term::Symbol(s) => Ok(s),
+        _ => Err(InterpError::Malformed(format!("{:?} isn't a symbol", term))),
+    }
+}
+
+fn add(a: Value, b: Value) -> Result<Value, InterpError> {
+    match (a, b) {
+        (Value::Num(a), Value::Num(b)) => a
+            .checked_add(b)
+            .map(Value::Num)
+            .ok_or(InterpError::Overflow),
+        (a, b) => Err(InterpError::Type(format!("can't add {} and {}", a, b))),
+    }
+}
+
+fn eq(a: Value, b: Value) -> Result<Value, InterpError> {
+    match (a, b) {
+        (Value::Num(a), Value::Num(b)) => Ok(Value::Bool(a == b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a == b)),
+        (a, b) => Err(InterpError::Type(format!("can't compare {} and {}", a, b))),
+    }
+}
+
+fn truth(cond: Value) -> Result<bool, InterpError> {
+    match cond {
+        Value::Bool(b) => Ok(b),
+        v => Err(InterpError::Type(format!("{} isn't a boolean", v))),
+    }
+}
+
+fn burn(fuel: &mut usize) -> Result<(), InterpError> {
+    *fuel = fuel.checked_sub(1).ok_or(InterpError::OutOfFuel)?;
+    Ok(())
+}
+
+/// Evaluates `expr` by substituting each value for its variable.
+pub fn interp(expr: &RecExpr<Lambda>) -> Result<Value, InterpError> {
+    eval_subst(&from_recexpr(expr), &mut FUEL.clone())
+}
+
+/// `value` as a term again, for substituting. Values of closed terms are
+/// closed, so substituting them never captures anything.
+fn value_term(value: &Value) -> Term {
+    match value {
+        Value::Num(n) => Term::Num(*n),
+        Value::Bool(b) => Term::Bool(*b),
+        Value::Closure { param, body, .. } => Term::Lambda(
+            Box::new(Term::Symbol(param.clone())),
+            Box::new(body.clone()),
+        ),
+        Value::Rec { var, expr, .. } => {
+            Term::Fix(Box::new(Term::Symbol(var.clone())), Box::new(expr.clone()))
+        }
+    }
+}
+
+/// `term` with the free occurrences of `v` replaced by `with`, which must be
+/// closed.
+fn subst(term: &Term, v: &str, with: &Term) -> Term {
+    let s = |t: &Term| Box::new(subst(t, v, with));
+    let binds_v = |binder: &Term| matches!(binder, Term::Symbol(b) if b == v);
+    match term {
+        Term::Var(x) if binds_v(x) => with.clone(),
+        Term::Lambda(x, _) | Term::Fix(x, _) | Term::LetRec(x, _, _) if binds_v(x) => term.clone(),
+        Term::Let(x, e, body) if binds_v(x) => Term::Let(x.clone(), s(e), body.clone()),
+        Term::Var(_) | Term::Bool(_) | Term::Num(_) | Term::Symbol(_) => term.clone(),
+        Term::Add(a, b) => Term::Add(s(a), s(b)),
+        Term::AddN(operands) => Term::AddN(operands.iter().map(|x| subst(x, v, with)).collect()),
+        Term::Eq(a, b) => Term::Eq(s(a), s(b)),
+        Term::App(f, x) => Term::App(s(f), s(x)),
+        Term::Lambda(x, body) => Term::Lambda(x.clone(), s(body)),
+        Term::Let(x, e, body) => Term::Let(x.clone(), s(e), s(body)),
+        Term::LetRec(x, e, body) => Term::LetRec(x.clone(), s(e), s(body)),
+        Term::Fix(x, body) => Term::Fix(x.clone(), s(body)),
+        Term::If(c, then, els) => Term::If(s(c), s(then), s(els)),
+    }
+}
+
+fn eval_subst(term: &Term, fuel: &mut usize) -> Result<Value, InterpError> {
+    burn(fuel)?;
+    match term {
+        Term::Num(n) => Ok(Value::Num(*n)),
+        Term::Bool(b) => Ok(Value::Bool(*b)),
+        Term::Symbol(s) => Err(InterpError::Malformed(format!("bare symbol {}", s))),
+        Term::Var(v) => Err(InterpError::Unbound(symbol(v)?.to_string())),
+        Term::Add(a, b) => add(eval_subst(a, fuel)?, eval_subst(b, fuel)?),
+        Term::AddN(operands) => operands
+            .iter()
+            .try_fold(Value::Num(0), |sum, x| add(sum, eval_subst(x, fuel)?)),
+        Term::Eq(a, b) => eq(eval_subst(a, fuel)?, eval_subst(b, fuel)?),
+        Term::If(c, then, els) => match truth(eval_subst(c, fuel)?)? {
+            true => eval_subst(then, fuel),
+            false => eval_subst(els, fuel),
+        },
+        Term::Lambda(v, body) => Ok(Value::Closure {
+            param: symbol(v)?.to_string(),
+            body: (**body).clone(),
+            env: Env::default(),
+        }),
+        Term::App(f, x) => match eval_subst(f, fuel)? {
+            Value::Closure { param, body, .. } => {
+                let arg = value_term(&eval_subst(x, fuel)?);
+                eval_subst(&subst(&body, &param, &arg), fuel)
+            }
+            f => Err(InterpError::Type(format!("can't apply {}", f))),
+        },
+        Term::Let(v, e, body) => {
+            let value = value_term(&eval_subst(e, fuel)?);
+            eval_subst(&subst(body, symbol(v)?, &value), fuel)
+        }
+        // unroll once: `(fix v e)` is `e` with `(fix v e)` for `v`
+        Term::Fix(v, e) => eval_subst(&subst(e, symbol(v)?, term), fuel),
+        Term::LetRec(v, e, body) => {
+            let fix = Term::Fix(v.clone(), e.clone());
+            eval_subst(&Term::Let(v.clone(), Box::new(fix), body.clone()), fuel)
+        }
+    }
+}
+
+/// Evaluates `expr` with an environment and closures.
+pub fn interp_env(expr: &RecExpr<Lambda>) -> Result<Value, InterpError> {
+    eval_env(&from_recexpr(expr), &Env::default(), &mut FUEL.clone())
+}
+
+fn eval_env(term: &Term, env: &Env, fuel: &mut usize) -> Result<Value, InterpError> {
+    burn(fuel)?;
+    let bind = |v: &Term, value: Value| -> Result<Env, InterpError> {
+        let mut env = env.clone();
+        env.insert(symbol(v)?.to_string(), value);
+        Ok(env)
+    };
+    match term {
+        Term::Num(n) => Ok(Value::Num(*n)),
+        Term::Bool(b) => Ok(Value::Bool(*b)),
+        Term::Symbol(s) => Err(InterpError::Malformed(format!("bare symbol {}", s))),
+        Term::Var(v) => {
+            let v = symbol(v)?;
+            match env.get(v) {
+                Some(Value::Rec {
+                    var,
+                    expr,
+                    env: rec_env,
+                }) => {
+                    let mut rec_env = rec_env.clone();
+                    rec_env.insert(var.clone(), env[v].clone());
+                    eval_env(expr, &rec_env, fuel)
+                }
+                Some(value) => Ok(value.clone()),
+                None => Err(InterpError::Unbound(v.to_string())),
+            }
+        }
+        Term::Add(a, b) => add(eval_env(a, env, fuel)?, eval_env(b, env, fuel)?),
+        Term::AddN(operands) => operands
+            .iter()
+            .try_fold(Value::Num(0), |sum, x| add(sum, eval_env(x, env, fuel)?)),
+        Term::Eq(a, b) => eq(eval_env(a, env, fuel)?, eval_env(b, env, fuel)?),
+        Term::If(c, then, els) => match truth(eval_env(c, env, fuel)?)? {
+            true => eval_env(then, env, fuel),
+            false => eval_env(els, env, fuel),
+        },
+        Term::Lambda(v, body) => Ok(Value::Closure {
+            param: symbol(v)?.to_string(),
+            body: (**body).clone(),
+            env: env.clone(),
+        }),
+        Term::App(f, x) => match eval_env(f, env, fuel)? {
+            Value::Closure {
+                param,
+                body,
+                env: closure_env,
+            } => {
+                let mut closure_env = closure_env;
+                closure_env.insert(param, eval_env(x, env, fuel)?);
+                eval_env(&body, &closure_env, fuel)
+            }
+            f => Err(InterpError::Type(format!("can't apply {}", f))),
+        },
+        Term::Let(v, e, body) => eval_env(body, &bind(v, eval_env(e, env, fuel)?)?, fuel),
+        Term::Fix(v, e) => {
+            let rec = Value::Rec {
+                var: symbol(v)?.to_string(),
+                expr: (**e).clone(),
+                env: env.clone(),
+            };
+            eval_env(e, &bind(v, rec)?, fuel)
+        }
+        Term::LetRec(v, e, body) => {
+            let fix = eval_env(&Term::Fix(v.clone(), e.clone()), env, fuel)?;
+            eval_env(body, &bind(v, fix)?, fuel)
+        }
+    }
+}
+
+/// Checks `interp` and `interp_env` agree on each program in the corpus at
+/// `path` (see `check_corpus`), and on its expected result where the program
+/// evaluates. Programs with free variables are skipped. Returns a message
+/// for every disagreement.
+pub fn check_interpreters(path: &str) -> Result<(), Vec<String>> {
+    let (cases, mut failures) = read_corpus(path);
+    for (line, program, expected) in cases {
+        let by_subst = interp(&program);
+        let by_env = interp_env(&program);
+        match (&by_subst, &by_env) {
+            (Err(InterpError::Unbound(_)), Err(InterpError::Unbound(_))) => continue,
+            (Ok(a), Ok(b)) if a.same_observable(b) => (),
+            (Err(a), Err(b)) if a == b => continue,
+            _ => {
+                failures.push(format!(
+                    "{}:{}: {} gives {:?} by substitution but {:?} in an environment",
+                    path, line, program, by_subst, by_env
+                ));
+                continue;
+            }
+        }
+        let value = by_subst.unwrap();
+        match interp(&expected) {
+            Ok(result) if result.same_observable(&value) => (),
+            result => failures.push(format!(
+                "{}:{}: {} gives {} but its expected result {} gives {:?}",
+                path, line, program, value, expected, result
+            )),
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// Checks that the closed terms in each closed e-class of `egraph` evaluate
+/// to the same value under `interp`, which an unsound rewrite would break.
+/// Each e-node stands in for its e-class with the best terms for its
+/// children. Terms that fail to evaluate, say by running out of fuel, are
+/// left out. Returns a message for the first e-class that disagrees.
+pub fn check_semantic_consistency(
+    egraph: &EGraph,
+    interp: fn(&RecExpr<Lambda>) -> Result<Value, InterpError>,
+) -> Result<(), String> {
+    let extractor = Extractor::new(egraph, AstSize);
+    let mut classes: Vec<_> = egraph
+        .classes()
+        .filter(|c| c.data.free.is_empty())
+        .collect();
+    classes.sort_by_key(|class| class.id);
+    for class in classes {
+        let mut first: Option<(RecExpr<Lambda>, Value)> = None;
+        for node in &class.nodes {
+            let children = node
+                .children()
+                .iter()
+                .map(|&c| extractor.find_best(c).1)
+                .collect();
+            let term = with_children(node, children);
+            let value = match interp(&term) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            match &first {
+                None => first = Some((term, value)),
+                Some((a, va)) if !va.same_observable(&value) => {
+                    return Err(format!(
+                        "e-class {}: {} gives {} but {} gives {}",
+                        class.id, a, va, term, value
+                    ));
+                }
+                Some(_) => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `node` with its children replaced by `children`, in order.
+fn with_children(node: &Lambda, children: Vec<RecExpr<Lambda>>) -> RecExpr<Lambda> {
+    let mut expr = RecExpr::default();
+    let mut roots = vec![];
+    for child in children {
+        let offset = expr.as_ref().len();
+        let mut root = None;
+        for n in child.as_ref() {
+            let n = n
+                .clone()
+                .map_children(|c| Id::from(usize::from(c) + offset));
+            root = Some(expr.add(n));
+        }
+        roots.push(root.expect("empty RecExpr"));
+    }
+    let mut roots = roots.into_iter();
+    expr.add(node.clone().map_children(|_| roots.next().unwrap()));
+    expr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fib_is_consistent() {
+        let (runner, _) = lambda_bench2().saturate(&RunConfig::default()).unwrap();
+        check_semantic_consistency(&runner.egraph, interp).unwrap();
+    }
+
+    /// Unioning `(+ 0 1)` with `2` by hand, as an unsound rule would.
+    #[test]
+    fn bad_union_is_caught() {
+        let mut egraph = EGraph::new(LambdaAnalysis::default());
+        let one = egraph.add_expr(&"(+ 0 1)".parse().unwrap());
+        let two = egraph.add_expr(&"2".parse().unwrap());
+        egraph.union(one, two);
+        egraph.rebuild();
+        assert!(check_semantic_consistency(&egraph, interp).is_err());
+    }
+
+    /// `interp` and `interp_env` agree on every program in `corpus.txt`.
+    #[test]
+    fn interpreters_agree_on_corpus() {
+        if let Err(failures) = check_interpreters("corpus.txt") {
+            panic!("{} disagreements:\n{}", failures.len(), failures.join("\n"));
+        }
+    }
+}
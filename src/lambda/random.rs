@@ -0,0 +1,103 @@
+//! Random closed programs, for fuzzing and scaling studies.
+use super::*;
+
+/// A random closed term at most `depth` deep, the same every time for the
+/// same `seed`. It tracks the variables in scope so every `(var x)` is
+/// bound, and leaves out `fix` and `letrec` so programs terminate. Terms
+/// are well typed only by chance: `(+ true 1)` and `(app 3 x)` are fair
+/// game, and just don't fold.
+pub fn random_lambda(depth: usize, seed: u64) -> RecExpr<Lambda> {
+    let mut gen = Generator {
+        state: seed,
+        scope: vec![],
+        binders: 0,
+    };
+    to_recexpr(&gen.term(depth))
+}
+
+/// A benchmark for `random_lambda(depth, seed)`.
+pub fn lambda_bench_random(depth: usize, seed: u64) -> Bench<Lambda, LambdaAnalysis> {
+    let program = random_lambda(depth, seed).pretty(80);
+    program_bench(&format!("random{}-{}", depth, seed), &program).with_tags(&["random"])
+}
+
+struct Generator {
+    state: u64,
+    /// Variables bound where the next term goes, innermost last.
+    scope: Vec<String>,
+    /// Binders made so far, for fresh names.
+    binders: usize,
+}
+
+impl Generator {
+    fn below(&mut self, n: u64) -> u64 {
+        crate::splitmix64(&mut self.state) % n
+    }
+
+    fn leaf(&mut self) -> Term {
+        match self.below(3) {
+            0 if !self.scope.is_empty() => {
+                let i = self.below(self.scope.len() as u64) as usize;
+                Term::Var(Box::new(Term::Symbol(self.scope[i].clone())))
+            }
+            1 => Term::Bool(self.below(2) == 0),
+            _ => Term::Num(self.below(10) as i64),
+        }
+    }
+
+    /// A fresh variable bound around `body`, built with it in scope.
+    fn bind(&mut self, body: impl FnOnce(&mut Self) -> Term) -> (Box<Term>, Box<Term>) {
+        let v = format!("x{}", self.binders);
+        self.binders += 1;
+        self.scope.push(v.clone());
+        let body = body(self);
+        self.scope.pop();
+        (Box::new(Term::Symbol(v)), Box::new(body))
+    }
+
+    fn term(&mut self, depth: usize) -> Term {
+        if depth == 0 {
+            return self.leaf();
+        }
+        let sub = |gen: &mut Self| Box::new(gen.term(depth - 1));
+        match self.below(7) {
+            0 => self.leaf(),
+            1 => Term::Add(sub(self), sub(self)),
+            2 => Term::Eq(sub(self), sub(self)),
+            3 => Term::If(sub(self), sub(self), sub(self)),
+            4 => Term::App(sub(self), sub(self)),
+            5 => {
+                let (v, body) = self.bind(|gen| gen.term(depth - 1));
+                Term::Lambda(v, body)
+            }
+            _ => {
+                let e = sub(self);
+                let (v, body) = self.bind(|gen| gen.term(depth - 1));
+                Term::Let(v, e, body)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `random_lambda` makes closed terms, with nothing free at the root once
+    /// added to an e-graph, for seeds 0 to 99.
+    #[test]
+    fn random_terms_are_closed() {
+        for seed in 0..100 {
+            let expr = random_lambda(6, seed);
+            let mut egraph = EGraph::new(LambdaAnalysis::default());
+            let root = egraph.add_expr(&expr);
+            egraph.rebuild();
+            assert!(
+                egraph[root].data.free.is_empty(),
+                "seed {} made an open term: {}",
+                seed,
+                expr
+            );
+        }
+    }
+}
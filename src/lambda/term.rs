@@ -0,0 +1,190 @@
+//! A plain tree mirroring `Lambda`, for code that doesn't want to depend on
+//! egg's `RecExpr` and `Id`s.
+use super::*;
+
+/// `Lambda` with the children boxed in place of `Id`s. Like `Lambda`, binders
+/// are `Symbol` children, so every `RecExpr<Lambda>` has a `Term`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    Bool(bool),
+    Num(i64),
+    Var(Box<Term>),
+    Add(Box<Term>, Box<Term>),
+    AddN(Vec<Term>),
+    Eq(Box<Term>, Box<Term>),
+    App(Box<Term>, Box<Term>),
+    Lambda(Box<Term>, Box<Term>),
+    Let(Box<Term>, Box<Term>, Box<Term>),
+    LetRec(Box<Term>, Box<Term>, Box<Term>),
+    Fix(Box<Term>, Box<Term>),
+    If(Box<Term>, Box<Term>, Box<Term>),
+    Symbol(String),
+}
+
+/// The tree rooted at the last node of `expr`. Shared subterms are copied.
+pub fn from_recexpr(expr: &RecExpr<Lambda>) -> Term {
+    let nodes = expr.as_ref();
+    assert!(!nodes.is_empty(), "empty RecExpr");
+    term_at(nodes, Id::from(nodes.len() - 1))
+}
+
+fn term_at(nodes: &[Lambda], id: Id) -> Term {
+    let t = |id: &Id| Box::new(term_at(nodes, *id));
+    match &nodes[usize::from(id)] {
+        Lambda::Bool(b) => Term::Bool(*b),
+        Lambda::Num(n) => Term::Num(*n),
+        Lambda::Var(v) => Term::Var(t(v)),
+        Lambda::Add([a, b]) => Term::Add(t(a), t(b)),
+        Lambda::AddN(operands) => Term::AddN(operands.iter().map(|x| term_at(nodes, *x)).collect()),
+        Lambda::Eq([a, b]) => Term::Eq(t(a), t(b)),
+        Lambda::App([f, x]) => Term::App(t(f), t(x)),
+        Lambda::Lambda([v, body]) => Term::Lambda(t(v), t(body)),
+        Lambda::Let([v, e, body]) => Term::Let(t(v), t(e), t(body)),
+        Lambda::LetRec([v, e, body]) => Term::LetRec(t(v), t(e), t(body)),
+        Lambda::Fix([v, body]) => Term::Fix(t(v), t(body)),
+        Lambda::If([c, then, els]) => Term::If(t(c), t(then), t(els)),
+        Lambda::Symbol(s) => Term::Symbol(s.to_string()),
+    }
+}
+
+pub fn to_recexpr(term: &Term) -> RecExpr<Lambda> {
+    let mut expr = RecExpr::default();
+    add_term(&mut expr, term);
+    expr
+}
+
+fn add_term(expr: &mut RecExpr<Lambda>, term: &Term) -> Id {
+    let node = match term {
+        Term::Bool(b) => Lambda::Bool(*b),
+        Term::Num(n) => Lambda::Num(*n),
+        Term::Var(v) => Lambda::Var(add_term(expr, v)),
+        Term::Add(a, b) => Lambda::Add([add_term(expr, a), add_term(expr, b)]),
+        Term::AddN(operands) => {
+            let ids: Vec<Id> = operands.iter().map(|x| add_term(expr, x)).collect();
+            Lambda::AddN(ids.into())
+        }
+        Term::Eq(a, b) => Lambda::Eq([add_term(expr, a), add_term(expr, b)]),
+        Term::App(f, x) => Lambda::App([add_term(expr, f), add_term(expr, x)]),
+        Term::Lambda(v, body) => Lambda::Lambda([add_term(expr, v), add_term(expr, body)]),
+        Term::Let(v, e, body) => {
+            Lambda::Let([add_term(expr, v), add_term(expr, e), add_term(expr, body)])
+        }
+        Term::LetRec(v, e, body) => {
+            Lambda::LetRec([add_term(expr, v), add_term(expr, e), add_term(expr, body)])
+        }
+        Term::Fix(v, body) => Lambda::Fix([add_term(expr, v), add_term(expr, body)]),
+        Term::If(c, then, els) => {
+            Lambda::If([add_term(expr, c), add_term(expr, then), add_term(expr, els)])
+        }
+        Term::Symbol(s) => Lambda::Symbol(s.as_str().into()),
+    };
+    expr.add(node)
+}
+
+/// `expr` with its bound variables renamed by binding depth, so terms equal
+/// up to renaming bound variables come out the same. Free variables keep
+/// their names. The new names, like `_ 0`, have a space in them, which no
+/// parsed variable can, so they can't collide with a free `(var _0)`.
+pub fn alpha_canonical(expr: &RecExpr<Lambda>) -> RecExpr<Lambda> {
+    to_recexpr(&canonical_term(&from_recexpr(expr), &mut vec![]))
+}
+
+/// A hash of `expr` up to renaming bound variables. It only depends on the
+/// term, so it's the same from run to run.
+pub fn alpha_hash(expr: &RecExpr<Lambda>) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    canonical_term(&from_recexpr(expr), &mut vec![]).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Renames under `bound`, the variables bound so far, innermost last.
+fn canonical_term(term: &Term, bound: &mut Vec<String>) -> Term {
+    let canon = |t: &Term, bound: &mut Vec<String>| Box::new(canonical_term(t, bound));
+    match term {
+        Term::Var(v) => match &**v {
+            Term::Symbol(s) => match bound.iter().rposition(|b| b == s) {
+                Some(depth) => Term::Var(Box::new(bound_name(depth))),
+                None => term.clone(),
+            },
+            _ => Term::Var(canon(v, bound)),
+        },
+        Term::Lambda(v, body) => {
+            let (v, body) = canonical_binder(v, bound, |bound| canon(body, bound));
+            Term::Lambda(v, body)
+        }
+        Term::Fix(v, body) => {
+            let (v, body) = canonical_binder(v, bound, |bound| canon(body, bound));
+            Term::Fix(v, body)
+        }
+        // the bound expression is outside the binder's scope
+        Term::Let(v, e, body) => {
+            let e = canon(e, bound);
+            let (v, body) = canonical_binder(v, bound, |bound| canon(body, bound));
+            Term::Let(v, e, body)
+        }
+        // both are in the binder's scope
+        Term::LetRec(v, e, body) => {
+            let (v, (e, body)) =
+                canonical_binder(v, bound, |bound| (canon(e, bound), canon(body, bound)));
+            Term::LetRec(v, e, body)
+        }
+        Term::Add(a, b) => Term::Add(canon(a, bound), canon(b, bound)),
+        Term::AddN(operands) => {
+            Term::AddN(operands.iter().map(|x| canonical_term(x, bound)).collect())
+        }
+        Term::Eq(a, b) => Term::Eq(canon(a, bound), canon(b, bound)),
+        Term::App(f, x) => Term::App(canon(f, bound), canon(x, bound)),
+        Term::If(c, then, els) => Term::If(canon(c, bound), canon(then, bound), canon(els, bound)),
+        Term::Bool(_) | Term::Num(_) | Term::Symbol(_) => term.clone(),
+    }
+}
+
+/// The canonical name of the binder `v`, and what `scope` makes of the
+/// children `v` is bound in, with `v` bound.
+fn canonical_binder<T>(
+    v: &Term,
+    bound: &mut Vec<String>,
+    scope: impl FnOnce(&mut Vec<String>) -> T,
+) -> (Box<Term>, T) {
+    match v {
+        Term::Symbol(s) => {
+            bound.push(s.clone());
+            let children = scope(bound);
+            bound.pop();
+            (Box::new(bound_name(bound.len())), children)
+        }
+        _ => (Box::new(v.clone()), scope(bound)),
+    }
+}
+
+fn bound_name(depth: usize) -> Term {
+    Term::Symbol(format!("_ {}", depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bound variable renamed by depth doesn't come out the same as a
+    /// free variable that happens to have the name depth would give it.
+    #[test]
+    fn bound_names_avoid_free_ones() {
+        let bound: RecExpr<Lambda> = "(lam x (var x))".parse().unwrap();
+        let free: RecExpr<Lambda> = "(lam x (var _0))".parse().unwrap();
+        assert_ne!(alpha_canonical(&bound), alpha_canonical(&free));
+        assert_ne!(alpha_hash(&bound), alpha_hash(&free));
+        let renamed: RecExpr<Lambda> = "(lam y (var y))".parse().unwrap();
+        assert_eq!(alpha_hash(&bound), alpha_hash(&renamed));
+    }
+
+    /// Both children of a `letrec` see its variable under the one name.
+    #[test]
+    fn letrec_binds_both_children() {
+        let a: RecExpr<Lambda> = "(letrec f (app (var f) 1) (var f))".parse().unwrap();
+        let b: RecExpr<Lambda> = "(letrec g (app (var g) 1) (var g))".parse().unwrap();
+        assert_eq!(alpha_canonical(&a), alpha_canonical(&b));
+        let c: RecExpr<Lambda> = "(letrec g (app (var g) 1) (var f))".parse().unwrap();
+        assert_ne!(alpha_canonical(&a), alpha_canonical(&c));
+    }
+}
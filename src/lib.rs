@@ -0,0 +1,490 @@
+use egg::*;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+mod error;
+mod extract;
+pub mod lambda;
+#[cfg(feature = "lp")]
+mod lp;
+pub mod math;
+#[cfg(feature = "profile")]
+mod profile;
+mod report;
+mod run;
+mod suite;
+
+pub use error::*;
+pub use extract::*;
+#[cfg(feature = "lp")]
+pub use lp::*;
+#[cfg(feature = "profile")]
+pub use profile::*;
+pub use report::*;
+pub use run::*;
+pub use suite::*;
+
+#[derive(Clone, Debug)]
+pub struct Bench<L: Language, A: Analysis<L> + 'static> {
+    pub name: String,
+    pub start_exprs: Vec<String>,
+    pub analysis: A,
+    pub rules: Vec<Rewrite<L, A>>,
+    /// The patterns to time, as written in `patterns.csv`. Set them with
+    /// `with_bench_pats`, which keeps `compiled_pats` in step.
+    bench_pats: Vec<String>,
+    /// `bench_pats` compiled, filled in on first use. Clones share it.
+    compiled_pats: Arc<OnceLock<Vec<Pattern<L>>>>,
+    /// How many times `compiled_pats` was filled in, shared like it.
+    pattern_compiles: Arc<AtomicUsize>,
+    /// Pairs of terms that should end up equivalent. Both sides are added
+    /// before saturation and checked once the run is over.
+    pub seed_equivalences: Vec<(String, String)>,
+    /// Whether to union the seed pairs up front, or leave it to the rules.
+    pub union_seeds: bool,
+    /// Pairs of terms assumed equal, always unioned before saturation. See
+    /// `assume`.
+    pub assumptions: Vec<(String, String)>,
+    /// What the bench exercises, like "recursion", for running a subset.
+    pub tags: Vec<&'static str>,
+}
+
+impl<L: Language + FromOp, A: Analysis<L>> Bench<L, A> {
+    /// The compiled `bench_pats`. They're compiled the first time this is
+    /// called, and reused by later calls and by clones of the bench. A
+    /// pattern that doesn't parse is an `Error::Parse`, and nothing is kept.
+    pub fn patterns(&self) -> Result<&[Pattern<L>], Error> {
+        if let Some(pats) = self.compiled_pats.get() {
+            return Ok(pats);
+        }
+        let compiled = self
+            .bench_pats
+            .iter()
+            .map(|pat| {
+                pat.parse()
+                    .map_err(|e| Error::Parse(format!("bad pattern {}: {}", pat, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.compiled_pats.get_or_init(|| {
+            self.pattern_compiles.fetch_add(1, Ordering::Relaxed);
+            compiled
+        }))
+    }
+
+    /// The patterns to time, as written.
+    pub fn bench_pats(&self) -> &[String] {
+        &self.bench_pats
+    }
+
+    /// Swaps in `bench_pats`, with nothing compiled yet, so the bench never
+    /// times patterns compiled from the ones it had before.
+    pub fn with_bench_pats(self, bench_pats: Vec<String>) -> Self {
+        Self {
+            bench_pats,
+            compiled_pats: Default::default(),
+            pattern_compiles: Default::default(),
+            ..self
+        }
+    }
+
+    /// Swaps in `rules`, keeping the programs and patterns. This consumes the
+    /// bench rather than cloning its old rules; clone it first to keep both.
+    pub fn with_rules(self, rules: Vec<Rewrite<L, A>>) -> Self {
+        Self { rules, ..self }
+    }
+
+    /// Assumes `lhs` equals `rhs`, like `(var n)` and `5`, by unioning them
+    /// before the runner starts, to see what follows. Nothing checks the
+    /// assumption: a false one makes the e-graph unsound, and everything
+    /// derived from it equally so.
+    pub fn assume(mut self, lhs: &str, rhs: &str) -> Self {
+        self.assumptions.push((lhs.into(), rhs.into()));
+        self
+    }
+
+    pub fn with_tags(self, tags: &[&'static str]) -> Self {
+        Self {
+            tags: tags.to_vec(),
+            ..self
+        }
+    }
+
+    /// Checks the benchmark is well formed. egg keys scheduler state by rule
+    /// name, so two rules with the same name silently misbehave.
+    pub fn validate(&self) -> Result<(), Error> {
+        let duplicates = duplicate_rule_names(&self.rules);
+        if duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DuplicateRules(duplicates))
+        }
+    }
+
+    /// Adds and unions both sides of every assumption, then adds both sides
+    /// of every seed equivalence to `egraph`, unioning them if `union_seeds`
+    /// is set. A side that doesn't parse is an `Error::Parse`, and leaves
+    /// `egraph` with the sides before it added.
+    pub fn add_seeds(&self, egraph: &mut EGraph<L, A>) -> Result<Vec<(Id, Id)>, Error> {
+        let parse = |expr: &str| -> Result<RecExpr<L>, Error> {
+            expr.parse()
+                .map_err(|e| Error::Parse(format!("{}: {}", expr, e)))
+        };
+        for (a, b) in &self.assumptions {
+            let a = egraph.add_expr(&parse(a)?);
+            let b = egraph.add_expr(&parse(b)?);
+            egraph.union(a, b);
+        }
+        let mut seeds = vec![];
+        for (a, b) in &self.seed_equivalences {
+            let a = egraph.add_expr(&parse(a)?);
+            let b = egraph.add_expr(&parse(b)?);
+            if self.union_seeds {
+                egraph.union(a, b);
+            }
+            seeds.push((a, b));
+        }
+        egraph.rebuild();
+        Ok(seeds)
+    }
+
+    /// The seed equivalences that don't hold in `egraph`.
+    pub fn failed_seeds(&self, egraph: &EGraph<L, A>, seeds: &[(Id, Id)]) -> Vec<(String, String)> {
+        self.seed_equivalences
+            .iter()
+            .zip(seeds)
+            .filter(|(_, (a, b))| egraph.find(*a) != egraph.find(*b))
+            .map(|(seed, _)| seed.clone())
+            .collect()
+    }
+}
+
+/// The names that more than one of `rules` has, in order of first repeat.
+pub fn duplicate_rule_names<L: Language, A: Analysis<L>>(rules: &[Rewrite<L, A>]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut duplicates = vec![];
+    for rule in rules {
+        let name = rule.name().to_string();
+        if !seen.insert(name.clone()) && !duplicates.contains(&name) {
+            duplicates.push(name);
+        }
+    }
+    duplicates
+}
+
+/// The `rules` named in `only` (or all of them) minus those named in `skip`.
+/// Naming a rule that isn't in `rules` is an error.
+pub fn filter_rules<L: Language, A: Analysis<L>>(
+    rules: &[Rewrite<L, A>],
+    only: Option<&[String]>,
+    skip: &[String],
+) -> Result<Vec<Rewrite<L, A>>, Error> {
+    let names: Vec<String> = rules.iter().map(|r| r.name().to_string()).collect();
+    let unknown: Vec<String> = only
+        .unwrap_or(&[])
+        .iter()
+        .chain(skip)
+        .filter(|name| !names.contains(name))
+        .cloned()
+        .collect();
+    if !unknown.is_empty() {
+        return Err(Error::UnknownRule(unknown));
+    }
+    Ok(rules
+        .iter()
+        .zip(&names)
+        .filter(|(_, name)| only.map_or(true, |only| only.contains(name)) && !skip.contains(name))
+        .map(|(rule, _)| rule.clone())
+        .collect())
+}
+
+/// The next number from a splitmix64 generator at `state`, to avoid pulling
+/// in rand for shuffles and random programs.
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Shuffles `rules` in place, the same way every time for the same `seed`.
+/// Runs that extract different results under different seeds depend on
+/// the order rules are applied in.
+pub fn shuffle_rules<T>(rules: &mut [T], seed: u64) {
+    let mut state = seed;
+    for i in (1..rules.len()).rev() {
+        let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+        rules.swap(i, j);
+    }
+}
+
+/// Groups `probes` by e-class in `egraph`, clusters in order of their first
+/// probe. Probes that aren't in `egraph` yet are added, so the analysis can
+/// merge them, but the rules never see them.
+pub fn equivalence_classes<L: Language + FromOp, A: Analysis<L>>(
+    egraph: &mut EGraph<L, A>,
+    probes: &[&str],
+) -> Result<Vec<Vec<String>>, Error> {
+    let mut ids = vec![];
+    for probe in probes {
+        let expr: RecExpr<L> = probe
+            .parse()
+            .map_err(|e| Error::Parse(format!("{}: {}", probe, e)))?;
+        ids.push(egraph.add_expr(&expr));
+    }
+    egraph.rebuild();
+    let mut clusters: Vec<(Id, Vec<String>)> = vec![];
+    for (probe, id) in probes.iter().zip(ids) {
+        let id = egraph.find(id);
+        match clusters.iter_mut().find(|(class, _)| *class == id) {
+            Some((_, cluster)) => cluster.push(probe.to_string()),
+            None => clusters.push((id, vec![probe.to_string()])),
+        }
+    }
+    Ok(clusters.into_iter().map(|(_, cluster)| cluster).collect())
+}
+
+/// Saturates `start` and `goals` with `rules`, for at most `iter_limit`
+/// iterations, and checks every goal ended up equivalent to `start`, like
+/// egg's `test_fn!`. Errors with the goals that didn't.
+pub fn check_equiv<L: Language + FromOp, A: Analysis<L> + Default>(
+    start: &str,
+    goals: &[&str],
+    rules: &[Rewrite<L, A>],
+    iter_limit: usize,
+) -> Result<(), Error> {
+    let parse = |expr: &str| -> Result<RecExpr<L>, Error> {
+        expr.parse()
+            .map_err(|e| Error::Parse(format!("{}: {}", expr, e)))
+    };
+    let mut runner = Runner::default()
+        .with_iter_limit(iter_limit)
+        .with_expr(&parse(start)?);
+    for goal in goals {
+        runner = runner.with_expr(&parse(goal)?);
+    }
+    let runner = runner.run(rules);
+    let start_class = runner.egraph.find(runner.roots[0]);
+    let failed: Vec<String> = goals
+        .iter()
+        .zip(&runner.roots[1..])
+        .filter(|&(_, &root)| runner.egraph.find(root) != start_class)
+        .map(|(goal, _)| goal.to_string())
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::NotEquivalent {
+            start: start.into(),
+            goals: failed,
+            stop_reason: format!("{:?}", runner.stop_reason),
+        })
+    }
+}
+
+/// Whether `pat` matches anywhere in `egraph`, or an `Error::Parse` if it
+/// doesn't parse.
+pub fn contains_pattern<L: Language + FromOp, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    pat: &str,
+) -> Result<bool, Error> {
+    let pat: Pattern<L> = pat
+        .parse()
+        .map_err(|e| Error::Parse(format!("bad pattern {}: {}", pat, e)))?;
+    Ok(!pat.search(egraph).is_empty())
+}
+
+/// How many e-classes a search for `pat` could match at the root: those
+/// with an e-node of the same operator and arity as the pattern's root, or
+/// all of them if the root is a variable. egg doesn't count the e-classes its
+/// matcher actually looks at, so this stands in for that.
+pub fn pattern_candidates<L: Language, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    pat: &Pattern<L>,
+) -> usize {
+    match pat.ast.as_ref().last() {
+        Some(ENodeOrVar::ENode(root)) => egraph
+            .classes()
+            .filter(|class| class.nodes.iter().any(|node| root.matches(node)))
+            .count(),
+        _ => egraph.number_of_classes(),
+    }
+}
+
+/// Blanks out `;` line comments, keeping newlines and every other character
+/// in place so that offsets into the result match the original text.
+pub fn strip_comments(src: &str) -> String {
+    let mut in_comment = false;
+    src.chars()
+        .map(|c| match c {
+            '\n' => {
+                in_comment = false;
+                c
+            }
+            ';' => {
+                in_comment = true;
+                ' '
+            }
+            _ if in_comment => ' ',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Reads a program from a file, stripping comments.
+pub fn read_program(path: impl AsRef<Path>) -> Result<String, Error> {
+    Ok(strip_comments(&std::fs::read_to_string(path)?))
+}
+
+fn read_patterns(bench_name: &str) -> Result<Vec<String>, Error> {
+    let file = File::open("patterns.csv")?;
+    let reader = BufReader::new(file);
+    let mut pats = vec![];
+    for line in reader.lines().skip(1) {
+        let line = line?;
+        let line = line.trim();
+        if !(line.is_empty() || line.starts_with('#')) {
+            let fields: Vec<_> = line.split(',').map(|s| s.trim()).collect();
+            if fields[0] == bench_name {
+                let pat_string = fields.last().unwrap();
+                pats.push(pat_string.to_string())
+            }
+        }
+    }
+    Ok(pats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::{
+        lambda_bench0, lambda_bench1, program_bench, rules, Lambda, LambdaAnalysis,
+    };
+
+    /// A clone of `lambda_bench0` runs the same as the original. `Bench` is
+    /// `Clone` since egg's `Rewrite`s are (their searchers and appliers are
+    /// shared behind `Arc`s), so clones share rules rather than rebuilding
+    /// them.
+    #[test]
+    fn clone_runs_the_same() {
+        let bench = lambda_bench0();
+        let copy = bench.clone();
+        let config = RunConfig::default();
+        let report = bench.run(&config).unwrap();
+        let copy_report = copy.run(&config).unwrap();
+        assert_eq!(report.without_times(), copy_report.without_times());
+    }
+
+    /// Seed equivalences left to the rules are checked after the run, with a
+    /// warning for each one that doesn't hold. `(+ 1 2)` and `3` aren't
+    /// unioned up front, but are found equal.
+    #[test]
+    fn seeds_checked_after_run() {
+        let bench = Bench {
+            seed_equivalences: vec![
+                ("(+ 1 2)".into(), "3".into()),
+                ("(+ 1 2)".into(), "(+ 2 1)".into()),
+                ("(+ 1 2)".into(), "4".into()),
+            ],
+            union_seeds: false,
+            ..program_bench("seeds", "(+ 1 2)")
+        };
+        let report = bench.run(&RunConfig::default()).unwrap();
+        assert_eq!(
+            report.warnings,
+            ["seed equivalence does not hold: (+ 1 2) = 4"]
+        );
+    }
+
+    /// Assuming `(var x)` is 3 makes `(+ (var x) 1)` fold to 4.
+    #[test]
+    fn assumption_folds() {
+        let bench = program_bench("assume", "(+ (var x) 1)").assume("(var x)", "3");
+        let (runner, _) = bench.saturate(&RunConfig::default()).unwrap();
+        let (_, best) = Extractor::new(&runner.egraph, AstSize).find_best(runner.roots[0]);
+        assert_eq!(best.to_string(), "4");
+    }
+
+    /// `check_equiv` proves `(+ 1 2)` equal to a literal 3, written as a
+    /// list, and to its commuted sum, and names the goals it can't prove.
+    #[test]
+    fn check_equiv_goals() {
+        check_equiv("(+ 1 2)", &["(3)", "(+ 2 1)"], &rules(), 30).unwrap();
+        match check_equiv("(+ 1 2)", &["4"], &rules(), 30) {
+            Err(Error::NotEquivalent { goals, .. }) => assert_eq!(goals, ["4"]),
+            result => panic!("proved (+ 1 2) equal to 4: {:?}", result),
+        }
+    }
+
+    /// Constant folding merges `(+ 1 2)`, `3`, and `(+ 2 1)` as they're
+    /// added, so they make one cluster, in probe order.
+    #[test]
+    fn equivalence_classes_cluster() {
+        let mut egraph: EGraph<Lambda, LambdaAnalysis> = EGraph::default();
+        let clusters = equivalence_classes(&mut egraph, &["(+ 1 2)", "3", "(+ 2 1)"]).unwrap();
+        assert_eq!(clusters, vec![vec!["(+ 1 2)", "3", "(+ 2 1)"]]);
+        let clusters = equivalence_classes(&mut egraph, &["3", "4"]).unwrap();
+        assert_eq!(clusters.len(), 2);
+    }
+
+    /// A bench compiles its patterns once, however many clones use them,
+    /// and compiles the new ones after `with_bench_pats`.
+    #[test]
+    fn patterns_compile_once() {
+        let bench = lambda_bench0();
+        let compiles =
+            |bench: &Bench<Lambda, LambdaAnalysis>| bench.pattern_compiles.load(Ordering::Relaxed);
+        let n = bench.patterns().unwrap().len();
+        let clone = bench.clone();
+        assert_eq!(clone.patterns().unwrap().len(), n);
+        assert_eq!(compiles(&bench), 1);
+        let one = bench.with_bench_pats(vec!["(var ?x)".into()]);
+        assert_eq!(compiles(&one), 0);
+        assert_eq!(one.patterns().unwrap().len(), 1);
+        assert_eq!(compiles(&one), 1);
+        assert_eq!(compiles(&clone), 1);
+    }
+
+    /// A pattern that doesn't parse is an error, and isn't cached.
+    #[test]
+    fn bad_pattern_is_parse_error() {
+        let bench = lambda_bench0().with_bench_pats(vec!["(var ?x".into()]);
+        assert!(matches!(bench.patterns(), Err(Error::Parse(_))));
+        assert_eq!(bench.pattern_compiles.load(Ordering::Relaxed), 0);
+    }
+
+    /// A bench with `add-comm` twice is rejected, by name.
+    #[test]
+    fn duplicate_rules_rejected() {
+        let mut rules = rules();
+        let comm = rules
+            .iter()
+            .find(|r| r.name() == "add-comm")
+            .unwrap()
+            .clone();
+        rules.push(comm);
+        let e = lambda_bench0().with_rules(rules).validate().unwrap_err();
+        match &e {
+            Error::DuplicateRules(names) => assert_eq!(names, &["add-comm"]),
+            _ => panic!("wrong error: {}", e),
+        }
+        assert!(e.to_string().contains("add-comm"), "{}", e);
+    }
+
+    /// After saturating `lambda_bench1`, `repeat`'s base case leaves the
+    /// identity function in the e-graph, but no variable it never mentions.
+    #[test]
+    fn contains_identity() {
+        let (runner, _) = lambda_bench1().saturate(&RunConfig::default()).unwrap();
+        assert!(contains_pattern(&runner.egraph, "(lam i (var i))").unwrap());
+        assert!(!contains_pattern(&runner.egraph, "(var nowhere)").unwrap());
+        assert!(matches!(
+            contains_pattern(&runner.egraph, "(lam i"),
+            Err(Error::Parse(_))
+        ));
+    }
+}
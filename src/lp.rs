@@ -0,0 +1,135 @@
+//! Optimal DAG extraction as an integer linear program.
+use crate::*;
+use good_lp::{
+    constraint, default_solver, variable, variables, Expression, Solution, SolverModel, Variable,
+};
+use std::collections::HashMap;
+
+/// Extracts the smallest DAG for `root`, counting every shared e-node once,
+/// and returns its size along with it. Unlike the greedy `Extractor`, this
+/// accounts for sharing, at the price of solving an ILP, which errors if
+/// the solver fails.
+///
+/// Every e-node gets a 0/1 variable saying whether it's picked, and every
+/// e-class one saying whether it's needed. The root is needed, a needed
+/// e-class picks one of its e-nodes, and a picked e-node needs its children.
+/// Each e-class also gets a level that must be above its children's levels,
+/// which rules out cycles.
+pub fn optimal_dag_extract<L, A>(
+    egraph: &EGraph<L, A>,
+    root: Id,
+) -> Result<(usize, RecExpr<L>), Error>
+where
+    L: Language,
+    A: Analysis<L>,
+{
+    let big_m = (egraph.number_of_classes() + 1) as f64;
+    let mut vars = variables!();
+    let mut needed: HashMap<Id, Variable> = HashMap::new();
+    let mut level: HashMap<Id, Variable> = HashMap::new();
+    let mut picked: HashMap<Id, Vec<(&L, Variable)>> = HashMap::new();
+    for class in egraph.classes() {
+        needed.insert(class.id, vars.add(variable().binary()));
+        level.insert(class.id, vars.add(variable().min(0).max(big_m)));
+        let nodes = class.nodes.iter();
+        let nodes = nodes.map(|node| (node, vars.add(variable().binary())));
+        picked.insert(class.id, nodes.collect());
+    }
+
+    let objective: Expression = picked.values().flatten().map(|&(_, x)| x).sum();
+    let mut model = vars.minimise(objective).using(default_solver);
+    let root_needed = needed[&egraph.find(root)];
+    model = model.with(constraint!(root_needed == 1));
+    for (id, nodes) in &picked {
+        let class_needed = needed[id];
+        let any_picked: Expression = nodes.iter().map(|&(_, x)| x).sum();
+        model = model.with(constraint!(class_needed <= any_picked));
+        for &(node, x) in nodes {
+            for &child in node.children() {
+                let child = egraph.find(child);
+                let (child_needed, above, below) = (needed[&child], level[id], level[&child]);
+                model = model.with(constraint!(x <= child_needed));
+                model = model.with(constraint!(above - below + big_m - big_m * x >= 1));
+            }
+        }
+    }
+
+    let solution = model
+        .solve()
+        .map_err(|e| Error::Solver(format!("{:?}", e)))?;
+    let chosen: HashMap<Id, &L> = picked
+        .iter()
+        .filter_map(|(&id, nodes)| {
+            let mut nodes = nodes.iter();
+            let &(node, _) = nodes.find(|&&(_, x)| solution.value(x) > 0.5)?;
+            Some((id, node))
+        })
+        .collect();
+
+    let mut expr = RecExpr::default();
+    let mut built = HashMap::new();
+    build(egraph, &chosen, root, &mut expr, &mut built);
+    Ok((built.len(), expr))
+}
+
+fn build<L: Language, A: Analysis<L>>(
+    egraph: &EGraph<L, A>,
+    chosen: &HashMap<Id, &L>,
+    id: Id,
+    expr: &mut RecExpr<L>,
+    built: &mut HashMap<Id, Id>,
+) -> Id {
+    let id = egraph.find(id);
+    if let Some(&built_id) = built.get(&id) {
+        return built_id;
+    }
+    let node = chosen[&id]
+        .clone()
+        .map_children(|child| build(egraph, chosen, child, expr, built));
+    let built_id = expr.add(node);
+    built.insert(id, built_id);
+    built_id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::{lambda_bench0, lambda_bench2, Lambda, LambdaAnalysis};
+
+    /// The optimal DAG is never bigger than the greedy one.
+    #[test]
+    fn optimal_at_most_greedy() {
+        for bench in [lambda_bench0(), lambda_bench2()].iter() {
+            let (runner, _) = bench.saturate(&RunConfig::default()).unwrap();
+            for &root in &runner.roots {
+                let (optimal, _) = optimal_dag_extract(&runner.egraph, root).unwrap();
+                let greedy = greedy_dag_size(&runner.egraph, root);
+                assert!(
+                    optimal <= greedy,
+                    "{}: {} > {}",
+                    bench.name,
+                    optimal,
+                    greedy
+                );
+            }
+        }
+    }
+
+    /// A sum that repeats one operand is bigger as a tree than the other sum
+    /// in its e-class, so greedy extraction picks the other one, but smaller
+    /// as a DAG, since the repeat is shared.
+    #[test]
+    fn optimal_beats_greedy_on_sharing() {
+        let mut egraph: EGraph<Lambda, LambdaAnalysis> = EGraph::default();
+        let shared = "(+ (+ (var a) (var b)) (+ (var a) (var b)))";
+        let distinct = "(+ (var c) (+ (var d) (var e)))";
+        let root = egraph.add_expr(&shared.parse().unwrap());
+        let other = egraph.add_expr(&distinct.parse().unwrap());
+        egraph.union(root, other);
+        egraph.rebuild();
+        let (optimal, expr) = optimal_dag_extract(&egraph, root).unwrap();
+        let greedy = greedy_dag_size(&egraph, root);
+        assert_eq!((optimal, greedy), (6, 8));
+        assert_eq!(expr.to_string(), shared);
+    }
+}
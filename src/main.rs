@@ -1,41 +1,12 @@
 use egg::*;
+use egg_bench::*;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::sync::mpsc;
 use std::thread;
 use std::time::*;
 use std::*;
 
-mod lambda;
-mod math;
-
-#[derive(Clone, Debug)]
-pub struct Bench<L: Language, A: Analysis<L> + 'static> {
-    name: String,
-    start_exprs: Vec<&'static str>,
-    rules: Vec<Rewrite<L, A>>,
-    bench_pats: Vec<Pattern<L>>,
-}
-
-fn parse_patterns<L: FromOp>(bench_name: &str) -> Vec<Pattern<L>> {
-    let file = File::open("patterns.csv").unwrap();
-    let reader = BufReader::new(file);
-    let mut pats = vec![];
-    for line in reader.lines().skip(1) {
-        let line = line.unwrap();
-        let line = line.trim();
-        if !(line.is_empty() || line.starts_with('#')) {
-            let fields: Vec<_> = line.split(',').map(|s| s.trim()).collect();
-            if fields[0] == bench_name {
-                let pat_string = fields.last().unwrap();
-                pats.push(pat_string.parse().unwrap())
-            }
-        }
-    }
-    pats
-}
-
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
@@ -54,31 +25,42 @@ pub fn run_bench<L, A>(
     opt: &Opt,
     bench: Bench<L, A>,
     strategies: &[Strategy],
-    wtr: &mut csv::Writer<std::fs::File>,
+    wtr: &mut csv::Writer<File>,
 ) where
     A: Analysis<L> + Default + Clone + Send + Sync,
     L: Language + FromOp + Sync + Send + Display,
     <A as egg::Analysis<L>>::Data: Send + Clone,
     <L as egg::Language>::Operator: Send + Sync,
 {
-    let rules = bench.rules;
-    let pats = bench.bench_pats;
-    let mut egraph: EGraph<L, A> = Default::default();
+    bench
+        .validate()
+        .unwrap_or_else(|e| panic!("invalid bench {}: {}", bench.name, e));
+    let rules = &bench.rules;
+    let pats = bench
+        .patterns()
+        .unwrap_or_else(|e| panic!("invalid bench {}: {}", bench.name, e));
+    let mut egraph: EGraph<L, A> = EGraph::new(bench.analysis.clone());
     for node_limit in &opt.sizes {
         egraph.strategy = Strategy::GenericJoin;
         let mut runner: Runner<L, A> = egg::Runner::default().with_egraph(egraph);
         for expr in &bench.start_exprs {
             runner = runner.with_expr(&expr.parse().unwrap());
         }
+        let seeds = bench
+            .add_seeds(&mut runner.egraph)
+            .unwrap_or_else(|e| panic!("invalid bench {}: {}", bench.name, e));
 
         let runner = runner
             .with_node_limit(*node_limit)
             .with_iter_limit(1000)
             .with_time_limit(std::time::Duration::from_secs(4000))
-            .run(&rules);
+            .run(rules);
         runner.print_report();
         egraph = runner.egraph;
-        for pat in &pats {
+        for (a, b) in bench.failed_seeds(&egraph, &seeds) {
+            println!("!!!!!!! seed equivalence does not hold: {} = {}", a, b);
+        }
+        for pat in pats {
             let mut em_time = None;
             let mut gj_time = None;
             for strategy in strategies {
@@ -161,7 +143,7 @@ pub fn run_bench<L, A>(
 }
 
 use structopt::StructOpt;
-#[derive(Debug, StructOpt)]
+#[derive(Debug, Clone, StructOpt)]
 pub struct Opt {
     #[structopt(short, long, value_delimiter = ",", default_value = "math,lambda")]
     benchmarks: Vec<String>,
@@ -182,35 +164,662 @@ pub struct Opt {
     timeout: f64,
     #[structopt(long)]
     verbose: bool,
+    /// Operators the lambda analysis may constant-fold
+    #[structopt(long, value_delimiter = ",", default_value = "+,=")]
+    fold_ops: Vec<String>,
+    /// Saturate each benchmark once and print a report instead of timing patterns
+    #[structopt(long)]
+    report: bool,
+    /// Record the best extracted cost after every iteration (with --report)
+    #[structopt(long)]
+    track_convergence: bool,
+    /// Write the saturated lambda e-graph to this file as JSON (with --report)
+    #[structopt(long)]
+    export_json: Option<String>,
+    /// Write each benchmark's report as JSON and its iterations as CSV
+    /// under this directory (with --report)
+    #[structopt(long)]
+    artifact_dir: Option<std::path::PathBuf>,
+    /// Write a folded stacks profile of each benchmark's run, under
+    /// --artifact-dir if given (with --report)
+    #[cfg(feature = "profile")]
+    #[structopt(long)]
+    profile: bool,
+    /// Rewrite sums to one e-class id order in place of `add-comm` (lambda benchmarks)
+    #[structopt(long)]
+    canonical_ac: bool,
+    /// Flatten sums into n-ary `+*` in place of AC rules (lambda benchmarks)
+    #[structopt(long)]
+    nary_add: bool,
+    /// Only push lets into if conditions, not branches (lambda benchmarks)
+    #[structopt(long)]
+    lazy_let_if: bool,
+    /// Only unroll fixpoints where they're applied (lambda benchmarks)
+    #[structopt(long)]
+    lazy_fix: bool,
+    /// Unroll each fixpoint at most this many times (lambda benchmarks)
+    #[structopt(long)]
+    fix_depth: Option<usize>,
+    /// Stop once the best cost has dropped by less than --min-delta over
+    /// this many iterations
+    #[structopt(long)]
+    diminishing_window: Option<usize>,
+    /// The drop in best cost --diminishing-window asks for
+    #[structopt(long, default_value = "1")]
+    min_delta: u64,
+    /// Write the e-graph after each iteration to step<i>.dot files here
+    #[structopt(long)]
+    animate_dir: Option<std::path::PathBuf>,
+    /// Draw frames with more e-nodes than this as a placeholder
+    #[structopt(long, default_value = "200")]
+    animate_max_nodes: usize,
+    /// Also run the structural lambda rules backwards
+    #[structopt(long)]
+    bidirectional: bool,
+    /// Only use lambda rules with these tags (arith, subst, control)
+    #[structopt(long, value_delimiter = ",")]
+    rule_tags: Vec<lambda::RuleTag>,
+    /// Also benchmark the lambda programs in these files
+    #[structopt(long, value_delimiter = ",")]
+    programs: Vec<String>,
+    /// Give up on a benchmark after this many seconds (with --report)
+    #[structopt(long)]
+    bench_timeout: Option<f64>,
+    /// Run the lambda benchmarks and programs in parallel (with --report)
+    #[structopt(long)]
+    parallel_benches: bool,
+    /// How to extract results (ast-size, or optimal-dag with the lp feature)
+    #[structopt(long, default_value = "ast-size")]
+    cost_metric: CostMetric,
+    /// Only run the rules with these names (with --report)
+    #[structopt(long, value_delimiter = ",")]
+    only_rules: Option<Vec<String>>,
+    /// Don't run the rules with these names (with --report)
+    #[structopt(long, value_delimiter = ",")]
+    skip_rules: Vec<String>,
+    /// Shuffle the rules with this seed before running (with --report)
+    #[structopt(long)]
+    shuffle_rules_seed: Option<u64>,
+    /// Fail unless each benchmark saturates (with --report)
+    #[structopt(long)]
+    assert_saturates: bool,
+    /// Fail benchmarks that run fewer iterations than this (with --report)
+    #[structopt(long)]
+    min_iterations: Option<usize>,
+    /// Record every rule's match count per iteration (with --report)
+    #[structopt(long)]
+    track_matches: bool,
+    /// Fail if a lambda benchmark's program has free variables
+    #[structopt(long)]
+    require_closed: bool,
+    /// Check the benchmarks are well formed without running them
+    #[structopt(long)]
+    dry_run: bool,
+    /// Only run the named benchmarks tagged with this, like "recursion"
+    #[structopt(long)]
+    tag: Option<String>,
+    /// Time every rule's apply phase (with --report)
+    #[structopt(long)]
+    time_rules: bool,
+    /// Warn about rules taking more than this fraction of the apply time
+    /// (with --report)
+    #[structopt(long)]
+    warn_slow_rule: Option<f64>,
+    /// Cap on the fresh variables capture avoidance may introduce
+    #[structopt(long)]
+    fresh_var_limit: Option<usize>,
+    /// Count and time the lambda analysis' make, merge, and modify calls
+    /// (with --report)
+    #[structopt(long)]
+    count_analysis: bool,
+    /// Don't union folded constants into their e-classes in modify (lambda
+    /// benchmarks)
+    #[structopt(long)]
+    lazy_const_union: bool,
+    /// Only time each rule's search on the initial e-graph (with --report)
+    #[structopt(long)]
+    search_only: bool,
+    /// Extract from only the e-classes reachable from the roots (with --report)
+    #[structopt(long)]
+    prune_unreachable: bool,
+    /// Extract best first, queueing at most this many candidate e-nodes,
+    /// rather than with egg's Extractor (with --report)
+    #[structopt(long)]
+    streaming_extract: Option<usize>,
+    /// Break ties between equally small terms toward left or right nested
+    /// chains, then by a structural hash of the term (with --report)
+    #[structopt(long)]
+    tie_break: Option<Nesting>,
+    /// Record every union the rules make (with --report)
+    #[structopt(long)]
+    trace: bool,
+    /// Report this many pairs of rules that made the most e-nodes one after
+    /// the other (with --report)
+    #[structopt(long)]
+    rule_pairs: Option<usize>,
+    /// Also extract lambda benchmarks with these operator weights, like
+    /// lam=100,app=2 (with --report)
+    #[structopt(long)]
+    weights: Option<lambda::WeightedCost>,
+    /// Stop runs that go this many iterations without changing the e-graph
+    /// (with --report)
+    #[structopt(long)]
+    stall_limit: Option<usize>,
+    /// The scheduler's initial match limit, past which it bans a rule (with
+    /// --report)
+    #[structopt(long)]
+    match_limit: Option<usize>,
+    /// How many distinct e-nodes to expect the start expressions to add
+    /// (with --report)
+    #[structopt(long)]
+    initial_capacity: Option<usize>,
+    /// Compare what each lambda benchmark simplifies to against
+    /// <bench name>.expected in this directory (with --report)
+    #[structopt(long)]
+    golden_dir: Option<std::path::PathBuf>,
+    /// Rewrite the --golden-dir files rather than comparing against them
+    #[structopt(long)]
+    update_golden: bool,
+    /// Stop each run as soon as this pattern matches (with --report)
+    #[structopt(long)]
+    stop_when_pattern: Option<String>,
+    /// Print what each benchmark simplified to (with --report)
+    #[structopt(long)]
+    print_result: bool,
+    /// Check the lambda programs in this corpus simplify as expected before
+    /// benchmarking (see corpus.txt)
+    #[structopt(long)]
+    check_corpus: Option<String>,
+    /// Check the substitution and environment interpreters agree on the
+    /// closed programs in this corpus, and with their expected results
+    #[structopt(long)]
+    check_interpreters: Option<String>,
+    /// Append a row per report to this CSV, tagged with --run-id (with
+    /// --report)
+    #[structopt(long)]
+    csv_append: Option<String>,
+    /// The run id, like a commit hash, for --csv-append
+    #[structopt(long, default_value = "")]
+    run_id: String,
+    /// How to print reports: debug, or text or markdown for a table (with
+    /// --report)
+    #[structopt(long, default_value = "debug")]
+    format: ReportFormat,
+}
+
+/// How `--report` prints reports.
+#[derive(Debug, Clone, Copy)]
+enum ReportFormat {
+    /// Each report in full, as soon as it's done
+    Debug,
+    /// A table of all the reports at the end
+    Text,
+    /// A markdown table of all the reports at the end
+    Markdown,
+}
+
+impl str::FromStr for ReportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(ReportFormat::Debug),
+            "text" => Ok(ReportFormat::Text),
+            "markdown" => Ok(ReportFormat::Markdown),
+            _ => Err(format!(
+                "format should be debug, text, or markdown, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// The `RunConfig` the options ask for.
+fn run_config(opt: &Opt) -> RunConfig {
+    RunConfig {
+        track_convergence: opt.track_convergence,
+        cost_metric: opt.cost_metric,
+        only_rules: opt.only_rules.clone(),
+        skip_rules: opt.skip_rules.clone(),
+        shuffle_rules_seed: opt.shuffle_rules_seed,
+        assert_saturates: opt.assert_saturates,
+        min_iterations: opt.min_iterations,
+        track_matches: opt.track_matches,
+        time_rules: opt.time_rules,
+        warn_slow_rule: opt.warn_slow_rule,
+        search_only: opt.search_only,
+        prune_unreachable: opt.prune_unreachable,
+        extract_mode: match (opt.streaming_extract, opt.tie_break) {
+            (Some(max_working_set), _) => ExtractMode::Streaming { max_working_set },
+            (None, Some(nesting)) => ExtractMode::TieBreak(nesting),
+            (None, None) => ExtractMode::Full,
+        },
+        trace: opt.trace,
+        rule_pairs: opt.rule_pairs,
+        artifact_dir: opt.artifact_dir.clone(),
+        animate_dir: opt.animate_dir.clone(),
+        animate_max_nodes: opt.animate_max_nodes,
+        stop_when_pattern: opt.stop_when_pattern.clone(),
+        stall_limit: opt.stall_limit,
+        match_limit: opt.match_limit,
+        initial_capacity: opt.initial_capacity,
+        stop_policy: opt
+            .diminishing_window
+            .map(|window| StopPolicy::DiminishingReturns {
+                window,
+                min_delta: opt.min_delta,
+            }),
+        #[cfg(feature = "profile")]
+        profile: opt.profile,
+        ..Default::default()
+    }
 }
 
-fn math(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<std::fs::File>) {
-    run_bench(opt, math::math_bench(), strategies, wtr)
+/// Either saturates the benchmark once and returns a report (`--report`),
+/// passing the runner to `inspect` to add to the report, or runs the
+/// pattern matching benchmark on it. The benchmark is built by `make_bench`
+/// so that it can be built on the thread that runs it when `--bench-timeout`
+/// is given.
+fn run<L, A, F>(
+    opt: &Opt,
+    name: &str,
+    make_bench: F,
+    inspect: fn(&Opt, &Runner<L, A>, &mut BenchReport),
+    strategies: &[Strategy],
+    wtr: &mut csv::Writer<File>,
+) -> Option<BenchReport>
+where
+    A: Analysis<L> + Default + Clone + Send + Sync + 'static,
+    L: Language + FromOp + Sync + Send + Display + 'static,
+    <A as egg::Analysis<L>>::Data: Send + Clone,
+    <L as egg::Language>::Operator: Send + Sync,
+    F: FnOnce() -> Bench<L, A> + Send + 'static,
+{
+    let config = run_config(opt);
+    if opt.dry_run {
+        let bench = make_bench();
+        bench
+            .dry_run(&config)
+            .unwrap_or_else(|e| panic!("invalid bench {}: {}", bench.name, e));
+        println!("{} is valid", bench.name);
+        return None;
+    }
+    if !opt.report {
+        run_bench(opt, make_bench(), strategies, wtr);
+        return None;
+    }
+    let timeout = opt.bench_timeout;
+    let opt = opt.clone();
+    let report_bench = move || {
+        let bench = make_bench();
+        let (runner, mut report) = bench
+            .saturate(&config)
+            .unwrap_or_else(|e| panic!("failed to run {}: {}", bench.name, e));
+        inspect(&opt, &runner, &mut report);
+        let path = config
+            .artifact_path(&report.name, "json")
+            .unwrap_or_else(|e| panic!("failed to write {}'s report: {}", report.name, e));
+        if let Some(path) = path {
+            let json = report.to_json();
+            std::fs::write(&path, json)
+                .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+        }
+        report
+    };
+    let report = match timeout {
+        Some(secs) => with_timeout(Duration::from_secs_f64(secs), report_bench)
+            .unwrap_or_else(|| BenchReport::timed_out(name)),
+        None => report_bench(),
+    };
+    print_report(&opt, &report);
+    Some(report)
 }
 
-fn lambda(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<std::fs::File>) {
-    run_bench(opt, lambda::lambda_bench(), strategies, wtr)
+/// Prints `report` as soon as it's done, if `--format` or `--print-result`
+/// ask for that.
+fn print_report(opt: &Opt, report: &BenchReport) {
+    if let ReportFormat::Debug = opt.format {
+        println!("{:#?}", report);
+    }
+    if opt.print_result {
+        for result in &report.results {
+            println!("{} simplified to {}", report.name, result);
+        }
+    }
+}
+
+fn math(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run(opt, "math", math::math_bench, |_, _, _| (), strategies, wtr)
+}
+
+/// Runs the lambda `--benchmarks` and `--programs` as one `BenchSuite` in
+/// parallel, for `--parallel-benches`. The reports don't get what
+/// `inspect_lambda` adds from the final e-graph, besides the analysis stats.
+fn run_parallel(opt: &Opt) -> Vec<BenchReport> {
+    let setup = lambda_setup(opt);
+    let mut benches = vec![];
+    for name in &opt.benchmarks {
+        if let Some(tag) = &opt.tag {
+            if !bench_tags(name).contains(&tag.as_str()) {
+                continue;
+            }
+        }
+        let bench = lambda::bench_by_name(name)
+            .unwrap_or_else(|e| panic!("{} (--parallel-benches only runs lambda benchmarks)", e));
+        benches.push(setup(bench));
+    }
+    for path in &opt.programs {
+        let bench = lambda::lambda_bench_file(path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", path, e));
+        benches.push(setup(bench));
+    }
+    let reports = BenchSuite::new(benches)
+        .run_all_parallel_with_stats(&run_config(opt))
+        .unwrap_or_else(|e| panic!("failed to run the benchmarks: {}", e));
+    for report in &reports {
+        print_report(opt, report);
+    }
+    reports
+}
+
+type LambdaBench = Bench<lambda::Lambda, lambda::LambdaAnalysis>;
+
+/// Checks a lambda bench and applies the options that change its analysis
+/// and rules.
+fn lambda_setup(opt: &Opt) -> impl Fn(LambdaBench) -> LambdaBench + Send + 'static {
+    let fold_ops = opt.fold_ops.clone();
+    let rule_tags = opt.rule_tags.clone();
+    let canonical_ac = opt.canonical_ac;
+    let nary_add = opt.nary_add;
+    let lazy_let_if = opt.lazy_let_if;
+    let lazy_fix = opt.lazy_fix;
+    let fix_depth = opt.fix_depth;
+    let bidirectional = opt.bidirectional;
+    let require_closed = opt.require_closed;
+    let fresh_var_limit = opt.fresh_var_limit;
+    let count_calls = opt.count_analysis;
+    let const_union = !opt.lazy_const_union;
+    move |mut bench: LambdaBench| {
+        bench
+            .check_binders()
+            .unwrap_or_else(|e| panic!("invalid bench {}: {}", bench.name, e));
+        if require_closed {
+            bench
+                .check_closed()
+                .unwrap_or_else(|e| panic!("invalid bench {}: {}", bench.name, e));
+        }
+        bench.analysis = lambda::LambdaAnalysis {
+            fresh_var_limit,
+            count_calls,
+            const_union,
+            ..lambda::LambdaAnalysis::with_fold_ops(&fold_ops)
+        };
+        if !rule_tags.is_empty() {
+            bench = bench.with_rules(lambda::rules_for(&rule_tags));
+        }
+        if canonical_ac {
+            bench.rules = lambda::canonical_ac(bench.rules);
+        }
+        if nary_add {
+            bench.rules = lambda::nary_add(bench.rules);
+        }
+        if lazy_let_if {
+            bench.rules = lambda::lazy_let_if(bench.rules);
+        }
+        if lazy_fix {
+            bench.rules = lambda::lazy_fix(bench.rules);
+        }
+        if let Some(depth) = fix_depth {
+            bench.rules = lambda::bounded_fix(bench.rules, depth);
+        }
+        if bidirectional {
+            bench.rules = lambda::bidirectional(bench.rules);
+        }
+        bench
+    }
+}
+
+fn run_lambda<F>(
+    opt: &Opt,
+    name: &str,
+    make_bench: F,
+    strategies: &[Strategy],
+    wtr: &mut csv::Writer<File>,
+) -> Option<BenchReport>
+where
+    F: FnOnce() -> Bench<lambda::Lambda, lambda::LambdaAnalysis> + Send + 'static,
+{
+    let setup = lambda_setup(opt);
+    let make_bench = move || {
+        let bench = setup(make_bench());
+        lambda::reset_analysis_stats();
+        bench
+    };
+    run(opt, name, make_bench, inspect_lambda, strategies, wtr)
+}
+
+fn inspect_lambda(
+    opt: &Opt,
+    runner: &Runner<lambda::Lambda, lambda::LambdaAnalysis>,
+    report: &mut BenchReport,
+) {
+    if let Some(path) = &opt.export_json {
+        std::fs::write(path, lambda::export_egraph_json(&runner.egraph)).unwrap();
+    }
+    if runner.egraph.analysis.fix_depth_hit {
+        let warning = "fixpoint unrolling hit --fix-depth";
+        report.warnings.push(warning.to_string());
+    }
+    if runner.egraph.analysis.fresh_var_limit_hit {
+        let warning = "capture avoidance hit --fresh-var-limit";
+        report.warnings.push(warning.to_string());
+    }
+    if let Some(weights) = &opt.weights {
+        let extractor = Extractor::new(&runner.egraph, weights.clone());
+        let costs = runner.roots.iter().map(|&root| extractor.find_best(root).0);
+        report.weighted_cost = Some(costs.sum());
+    }
+    if let Some(dir) = &opt.golden_dir {
+        let path = dir.join(format!("{}.expected", report.name));
+        let actual = lambda::extracted_canonical(&runner.egraph, &runner.roots);
+        if let Err(diff) = lambda::check_golden(&actual, &path, opt.update_golden) {
+            panic!("{}", diff);
+        }
+    }
+    report.operator_counts = lambda::operator_counts(&runner.egraph)
+        .into_iter()
+        .map(|(op, n)| (op.to_string(), n))
+        .collect();
+    if runner.egraph.analysis.count_calls {
+        report.analysis_stats = Some(lambda::analysis_stats());
+    }
+}
+
+fn lambda(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(opt, "lambda", lambda::lambda_bench, strategies, wtr)
+}
+
+fn lambda0(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(opt, "lambda0", lambda::lambda_bench0, strategies, wtr)
+}
+
+fn lambda1(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(opt, "lambda1", lambda::lambda_bench1, strategies, wtr)
+}
+
+fn lambda2(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(opt, "lambda2", lambda::lambda_bench2, strategies, wtr)
+}
+
+fn letrec(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(opt, "letrec", lambda::lambda_bench_letrec, strategies, wtr)
+}
+
+fn random(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(
+        opt,
+        "random",
+        || lambda::bench_by_name("random").unwrap(),
+        strategies,
+        wtr,
+    )
+}
+
+fn sum(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(
+        opt,
+        "sum",
+        || lambda::bench_by_name("sum").unwrap(),
+        strategies,
+        wtr,
+    )
+}
+
+fn manyvars(
+    opt: &Opt,
+    strategies: &[Strategy],
+    wtr: &mut csv::Writer<File>,
+) -> Option<BenchReport> {
+    run_lambda(
+        opt,
+        "manyvars",
+        || lambda::bench_by_name("manyvars").unwrap(),
+        strategies,
+        wtr,
+    )
+}
+
+fn wide(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(
+        opt,
+        "wide",
+        || lambda::bench_by_name("wide").unwrap(),
+        strategies,
+        wtr,
+    )
+}
+
+fn onestep(opt: &Opt, strategies: &[Strategy], wtr: &mut csv::Writer<File>) -> Option<BenchReport> {
+    run_lambda(
+        opt,
+        "onestep",
+        lambda::lambda_bench_onestep,
+        strategies,
+        wtr,
+    )
+}
+
+fn constfold(
+    opt: &Opt,
+    strategies: &[Strategy],
+    wtr: &mut csv::Writer<File>,
+) -> Option<BenchReport> {
+    let opt = Opt {
+        count_analysis: true,
+        ..opt.clone()
+    };
+    let report = run_lambda(
+        &opt,
+        "constfold",
+        || lambda::bench_by_name("constfold").unwrap(),
+        strategies,
+        wtr,
+    );
+    if let Some(stats) = report.as_ref().and_then(|r| r.analysis_stats.as_ref()) {
+        let total = report.as_ref().unwrap().total_time;
+        println!(
+            "constfold: modify took {:.1}% of the run",
+            100.0 * stats.modify.time / total
+        );
+    }
+    report
+}
+
+/// The tags of the bench `name` runs, without running it.
+fn bench_tags(name: &str) -> Vec<&'static str> {
+    if name == "math" {
+        math::math_bench().tags
+    } else {
+        lambda::bench_by_name(name)
+            .map(|bench| bench.tags)
+            .unwrap_or_default()
+    }
 }
 
 fn main() {
     let start = Instant::now();
     let _ = env_logger::init();
     let opt = Opt::from_args();
+    if let Some(path) = &opt.check_corpus {
+        if let Err(failures) = lambda::check_corpus(path) {
+            panic!("corpus cases failed:\n{}", failures.join("\n"));
+        }
+        println!("all cases in {} simplify as expected", path);
+    }
+    if let Some(path) = &opt.check_interpreters {
+        if let Err(failures) = lambda::check_interpreters(path) {
+            panic!("interpreters disagree:\n{}", failures.join("\n"));
+        }
+        println!("interpreters agree on {}", path);
+    }
     let strategies = match opt.strategy.as_str() {
         "all" => vec![Strategy::GenericJoin, Strategy::EMatch],
         "gj" => vec![Strategy::GenericJoin],
         "em" => vec![Strategy::EMatch],
         _ => panic!("strategy should be one of all, gj, or em"),
     };
-    let out = std::fs::File::create(&opt.filename).unwrap();
+    let out = File::create(&opt.filename).unwrap();
     let mut wtr = csv::Writer::from_writer(out);
-    let mut bench_collection: collections::HashMap<String, fn(_, _, &mut _)> = Default::default();
+    let mut bench_collection: collections::HashMap<String, fn(_, _, &mut _) -> _> =
+        Default::default();
     bench_collection.insert("math".into(), math);
     bench_collection.insert("lambda".into(), lambda);
+    bench_collection.insert("lambda0".into(), lambda0);
+    bench_collection.insert("lambda1".into(), lambda1);
+    bench_collection.insert("lambda2".into(), lambda2);
+    bench_collection.insert("letrec".into(), letrec);
+    bench_collection.insert("onestep".into(), onestep);
+    bench_collection.insert("sum".into(), sum);
+    bench_collection.insert("random".into(), random);
+    bench_collection.insert("manyvars".into(), manyvars);
+    bench_collection.insert("constfold".into(), constfold);
+    bench_collection.insert("wide".into(), wide);
+    let mut reports = vec![];
     for _ in 0..opt.samples {
+        if opt.parallel_benches {
+            reports.extend(run_parallel(&opt));
+            continue;
+        }
         for bench in &opt.benchmarks {
-            let bench_fn = &bench_collection[&bench.clone()];
-            bench_fn(&opt, &strategies, &mut wtr);
+            let bench_fn = bench_collection
+                .get(bench)
+                .unwrap_or_else(|| panic!("{}", Error::UnknownBench(bench.clone())));
+            if let Some(tag) = &opt.tag {
+                if !bench_tags(bench).contains(&tag.as_str()) {
+                    continue;
+                }
+            }
+            reports.extend(bench_fn(&opt, &strategies, &mut wtr));
+        }
+        for path in &opt.programs {
+            let make_bench = {
+                let path = path.clone();
+                move || {
+                    lambda::lambda_bench_file(&path)
+                        .unwrap_or_else(|e| panic!("failed to read {}: {}", path, e))
+                }
+            };
+            reports.extend(run_lambda(&opt, path, make_bench, &strategies, &mut wtr));
+        }
+    }
+    match opt.format {
+        ReportFormat::Debug => (),
+        ReportFormat::Text => print!("{}", format_table(&reports)),
+        ReportFormat::Markdown => print!("{}", format_markdown(&reports)),
+    }
+    if let Some(path) = &opt.csv_append {
+        for report in &reports {
+            append_csv(report, path, &opt.run_id)
+                .unwrap_or_else(|e| panic!("failed to append to {}: {}", path, e));
         }
     }
 
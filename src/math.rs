@@ -6,6 +6,7 @@ use std::cmp::Ordering;
 pub fn math_bench() -> Bench<Math, ConstantFold> {
     Bench {
         name: "math".into(),
+        analysis: ConstantFold,
         start_exprs: vec![
             "(i (ln x) x)",
             "(i (+ x (cos x)) x)",
@@ -14,9 +15,19 @@ pub fn math_bench() -> Bench<Math, ConstantFold> {
             "(d x (- (pow x 3) (* 7 (pow x 2))))",
             "(+ (* y (+ x y)) (- (+ x 2) (+ x x)))",
             "(/ 1 (- (/ (+ 1 (sqrt five)) 2) (/ (- 1 (sqrt five)) 2)))",
-        ],
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
         rules: rules(),
-        bench_pats: parse_patterns("math"),
+        seed_equivalences: vec![],
+        union_seeds: true,
+        assumptions: vec![],
+        bench_pats: read_patterns("math")
+            .unwrap_or_else(|e| panic!("can't read patterns.csv: {}", e)),
+        compiled_pats: Default::default(),
+        pattern_compiles: Default::default(),
+        tags: vec!["math"],
     }
 }
 
@@ -0,0 +1,79 @@
+//! Sampling profiles of runs, in the folded stacks format that `inferno`
+//! (or `flamegraph.pl`) renders.
+use crate::Error;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+/// How often to sample, in Hz.
+const FREQUENCY: i32 = 1000;
+
+/// Runs `f` under a sampling profiler and writes what it saw to `path`, one
+/// `thread;outermost;...;innermost count` line per distinct stack. If the
+/// profiler can't start, `f` isn't run; if the profile can't be built or
+/// written, what `f` returned is lost. Either way the error is an
+/// `Error::Io`.
+pub fn profiled<T>(path: &Path, f: impl FnOnce() -> T) -> Result<T, Error> {
+    let profiler_error = |what: &str, e: pprof::Error| {
+        Error::Io(io::Error::new(
+            io::ErrorKind::Other,
+            format!("failed to {}: {}", what, e),
+        ))
+    };
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|e| profiler_error("start the profiler", e))?;
+    let result = f();
+    let report = guard
+        .report()
+        .build()
+        .map_err(|e| profiler_error("build the profile", e))?;
+    let mut folded = String::new();
+    for (stack, count) in &report.data {
+        let mut line = stack.thread_name.clone();
+        for frame in stack.frames.iter().rev() {
+            for symbol in frame.iter().rev() {
+                write!(line, ";{}", symbol).unwrap();
+            }
+        }
+        writeln!(folded, "{} {}", line, count).unwrap();
+    }
+    std::fs::write(path, folded).map_err(|e| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!("failed to write {}: {}", path.display(), e),
+        ))
+    })?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::lambda_bench0;
+    use crate::RunConfig;
+    use std::time::{Duration, Instant};
+
+    /// Profiling runs of `lambda_bench0` for long enough to be sampled
+    /// writes some stacks.
+    #[test]
+    fn profile_written() {
+        let path = std::env::temp_dir().join(format!("egg-bench-{}.folded", std::process::id()));
+        let runs = profiled(&path, || {
+            let start = Instant::now();
+            let mut runs = 0;
+            while start.elapsed() < Duration::from_millis(200) {
+                lambda_bench0().run(&RunConfig::default()).unwrap();
+                runs += 1;
+            }
+            runs
+        })
+        .unwrap();
+        let folded = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(runs > 0);
+        assert!(!folded.is_empty());
+    }
+}
@@ -0,0 +1,486 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+/// What happened when a `Bench` was saturated by `Bench::run`. Reports
+/// round-trip through JSON (see `to_json`), so runs can be compared across
+/// machines and commits. Times are stored as seconds in `f64`s rather than
+/// as `Duration`s, which serde would write as a platform-neutral but
+/// awkward `{secs, nanos}` pair.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BenchReport {
+    pub name: String,
+    pub stop_reason: String,
+    /// Whether egg stopped with `StopReason::Saturated`: no rule had a
+    /// match left to add. A run can reach its best result long before
+    /// this, or never saturate at all; see `result_converged_at`.
+    pub saturated: bool,
+    pub iterations: usize,
+    pub nodes: usize,
+    pub classes: usize,
+    /// Total runner time in seconds.
+    pub total_time: f64,
+    /// `total_time` split into search, apply, and rebuild, in seconds. The
+    /// analysis runs during rebuilding.
+    pub search_time: f64,
+    pub apply_time: f64,
+    pub rebuild_time: f64,
+    /// Time spent parsing the start expressions in seconds.
+    pub parse_time: f64,
+    /// Time spent extracting `best_cost` and `results` in seconds.
+    pub extraction_time: f64,
+    /// The most candidates streaming extraction queued at once, with
+    /// `ExtractMode::Streaming`.
+    pub peak_working_set: Option<usize>,
+    /// Summed best `AstSize` of the roots before the first iteration and
+    /// after every iteration. Empty unless `track_convergence` is set.
+    pub convergence: Vec<usize>,
+    /// How many iterations had run when `convergence` reached the value it
+    /// ends on, no more than `iterations`. `None` unless
+    /// `track_convergence` is set.
+    pub result_converged_at: Option<usize>,
+    /// Number of e-nodes in the roots' e-classes (summed over roots), at the
+    /// same points as `convergence`. A spike means AC blowup at the root.
+    pub root_nodes: Vec<usize>,
+    /// How many times each rule was applied over the whole run.
+    pub rule_applications: BTreeMap<String, usize>,
+    /// Each rule's match count at the start of every iteration, when run
+    /// with `track_matches`. See `rules_over_match_limit`.
+    pub rule_matches: BTreeMap<String, Vec<usize>>,
+    /// Seconds each rule spent applying its matches, when run with
+    /// `time_rules`.
+    pub rule_apply_time: BTreeMap<String, f64>,
+    /// Seconds each rule took to search the initial e-graph, when run with
+    /// `search_only`.
+    pub rule_search_time: BTreeMap<String, f64>,
+    /// Every union the rules made, in order, when run with `trace`.
+    pub trace: Vec<TraceEntry>,
+    /// The pairs of rules that made the most e-nodes, most first, when run
+    /// with `rule_pairs`.
+    pub rule_pairs: Vec<RulePair>,
+    /// The `bench_pats` that match nothing in the final e-graph.
+    pub zero_match_pats: Vec<String>,
+    /// How each of the `bench_pats` fared against the final e-graph.
+    pub pattern_stats: Vec<PatternStats>,
+    /// Anything about the run worth a second look, like slow rules.
+    pub warnings: Vec<String>,
+    /// Sum of the best `AstSize` over the roots.
+    pub best_cost: usize,
+    /// `best_cost` over the `AstSize` of the start expressions it was
+    /// extracted from, so below 1 when the rules shrank the programs.
+    pub simplification_ratio: f64,
+    /// The best term for each root, pretty printed.
+    pub results: Vec<String>,
+    /// Best cost under the `--weights` given to a lambda benchmark.
+    pub weighted_cost: Option<u64>,
+    /// E-classes left out of extraction with `prune_unreachable`.
+    pub pruned_classes: usize,
+    /// Size of the greedy `AstSize` extraction as a DAG, summed over roots.
+    pub greedy_dag_cost: usize,
+    /// Size of the optimal DAG, summed over roots, when extracting with
+    /// `CostMetric::OptimalDag`.
+    pub optimal_dag_cost: Option<usize>,
+    /// Whether the run was abandoned by `with_timeout`.
+    pub timed_out: bool,
+    /// How many iterations had run when `stop_when_pattern` first matched,
+    /// or `None` if it never did.
+    pub pattern_found_at: Option<usize>,
+    /// Calls into the analysis, for analyses that count them.
+    pub analysis_stats: Option<AnalysisStats>,
+    /// How many e-nodes of the final e-graph have each operator, for
+    /// languages that name their operators like the lambda benches do.
+    pub operator_counts: BTreeMap<String, usize>,
+}
+
+/// Calls to an analysis' `make`, `merge`, and `modify`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnalysisStats {
+    pub make: CallStats,
+    pub merge: CallStats,
+    pub modify: CallStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CallStats {
+    pub calls: usize,
+    /// Cumulative time in seconds.
+    pub time: f64,
+}
+
+/// Searching the final e-graph for one of the `bench_pats`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PatternStats {
+    pub pattern: String,
+    /// E-classes the pattern's root could match; see `pattern_candidates`.
+    pub candidates: usize,
+    /// E-classes it did match.
+    pub matched_classes: usize,
+    /// Matches over all those e-classes.
+    pub matches: usize,
+}
+
+/// A union made by applying `rule` to a match in `eclass`, leaving the two
+/// in the e-class `result`. E-class ids are as of the replay.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TraceEntry {
+    pub iteration: usize,
+    pub rule: String,
+    pub eclass: usize,
+    pub result: usize,
+}
+
+/// New e-nodes made by applying `second` to an e-class that `first` last
+/// added e-nodes to. This only looks at the e-class a match is rooted in, so
+/// growth enabled by a rule deeper in the match is credited to whatever
+/// rule last touched the root, and matches in e-classes that no rule has
+/// touched yet aren't counted at all.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RulePair {
+    pub first: String,
+    pub second: String,
+    pub nodes: usize,
+}
+
+impl BenchReport {
+    /// The report for a benchmark that didn't finish in time.
+    pub fn timed_out(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            stop_reason: "TimedOut".into(),
+            timed_out: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Reads back a report written by `to_json`.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|e| Error::Parse(e.to_string()))
+    }
+
+    /// Checks this report survives `to_json` and `from_json` unchanged.
+    pub fn check_roundtrip(&self) -> Result<(), String> {
+        let json = self.to_json();
+        let back = Self::from_json(&json).map_err(|e| e.to_string())?;
+        if &back == self {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} changed in a JSON round trip:\n{}",
+                self.name, json
+            ))
+        }
+    }
+
+    /// This report with every time zeroed, so reports of the same run on
+    /// the same bench compare equal.
+    pub fn without_times(&self) -> Self {
+        let zero = |times: &BTreeMap<String, f64>| times.keys().map(|k| (k.clone(), 0.0)).collect();
+        let mut report = Self {
+            total_time: 0.0,
+            search_time: 0.0,
+            apply_time: 0.0,
+            rebuild_time: 0.0,
+            parse_time: 0.0,
+            extraction_time: 0.0,
+            rule_apply_time: zero(&self.rule_apply_time),
+            rule_search_time: zero(&self.rule_search_time),
+            ..self.clone()
+        };
+        if let Some(stats) = &mut report.analysis_stats {
+            for call in &mut [&mut stats.make, &mut stats.merge, &mut stats.modify] {
+                call.time = 0.0;
+            }
+        }
+        report
+    }
+
+    /// How many times faster this run was than `baseline`, by `total_time`.
+    pub fn speedup_vs(&self, baseline: &BenchReport) -> f64 {
+        baseline.total_time / self.total_time
+    }
+
+    /// This run's iterations over `baseline`'s.
+    pub fn iteration_ratio(&self, baseline: &BenchReport) -> f64 {
+        self.iterations as f64 / baseline.iterations as f64
+    }
+
+    /// This run's final e-graph size in e-nodes over `baseline`'s.
+    pub fn size_ratio(&self, baseline: &BenchReport) -> f64 {
+        self.nodes as f64 / baseline.nodes as f64
+    }
+}
+
+const TABLE_HEADER: [&str; 8] = [
+    "name",
+    "saturated",
+    "iters",
+    "time(ms)",
+    "nodes",
+    "classes",
+    "best-cost",
+    "ratio",
+];
+
+impl BenchReport {
+    /// This report's row of the `format_table` and `format_markdown` tables.
+    fn table_row(&self) -> Vec<String> {
+        if self.timed_out {
+            let mut row = vec![self.name.clone(), "timed out".into()];
+            row.resize(TABLE_HEADER.len(), "-".into());
+            row
+        } else {
+            vec![
+                self.name.clone(),
+                if self.saturated { "yes" } else { "no" }.into(),
+                self.iterations.to_string(),
+                format!("{:.1}", self.total_time * 1000.0),
+                self.nodes.to_string(),
+                self.classes.to_string(),
+                self.best_cost.to_string(),
+                format!("{:.3}", self.simplification_ratio),
+            ]
+        }
+    }
+
+    /// This report as a one row markdown table.
+    pub fn to_markdown(&self) -> String {
+        format_markdown(std::slice::from_ref(self))
+    }
+}
+
+/// Lays `reports` out as a markdown table with a row per report, for
+/// `--format markdown`.
+pub fn format_markdown(reports: &[BenchReport]) -> String {
+    let mut rows = vec![TABLE_HEADER
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()];
+    rows.push(vec!["---".into(); TABLE_HEADER.len()]);
+    rows.extend(reports.iter().map(|report| report.table_row()));
+    rows.iter()
+        .map(|row| format!("| {} |\n", row.join(" | ")))
+        .collect()
+}
+
+/// Lays `reports` out as a table with a row per report, for `--format text`.
+pub fn format_table(reports: &[BenchReport]) -> String {
+    let mut rows = vec![TABLE_HEADER
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()];
+    rows.extend(reports.iter().map(|report| report.table_row()));
+    let widths: Vec<usize> = (0..TABLE_HEADER.len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap())
+        .collect();
+    rows.insert(1, widths.iter().map(|&w| "-".repeat(w)).collect());
+
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &w))| match i {
+                // text on the left, numbers on the right
+                0 | 1 => format!("{:<w$}", cell, w = w),
+                _ => format!("{:>w$}", cell, w = w),
+            })
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// A row of `append_csv`.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    run_id: &'a str,
+    name: &'a str,
+    stop_reason: &'a str,
+    saturated: bool,
+    iterations: usize,
+    nodes: usize,
+    classes: usize,
+    total_time: f64,
+    search_time: f64,
+    apply_time: f64,
+    rebuild_time: f64,
+    parse_time: f64,
+    extraction_time: f64,
+    best_cost: usize,
+    simplification_ratio: f64,
+}
+
+/// Appends a row for `report` to the CSV at `path`, tagged with `run_id`
+/// (a commit hash, say) so rows from many runs make a time series. The
+/// header is only written when the file is new or empty.
+pub fn append_csv(report: &BenchReport, path: impl AsRef<Path>, run_id: &str) -> Result<(), Error> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let is_new = file.metadata()?.len() == 0;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(is_new)
+        .from_writer(file);
+    wtr.serialize(CsvRow {
+        run_id,
+        name: &report.name,
+        stop_reason: &report.stop_reason,
+        saturated: report.saturated,
+        iterations: report.iterations,
+        nodes: report.nodes,
+        classes: report.classes,
+        total_time: report.total_time,
+        search_time: report.search_time,
+        apply_time: report.apply_time,
+        rebuild_time: report.rebuild_time,
+        parse_time: report.parse_time,
+        extraction_time: report.extraction_time,
+        best_cost: report.best_cost,
+        simplification_ratio: report.simplification_ratio,
+    })
+    .map_err(io::Error::from)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+/// E-graph sizes of two runs of one bench, from `Bench::compare_growth`.
+/// Row `i` is the number of e-nodes before iteration `i`, and the last row is
+/// the final size. The run that stopped first keeps its final size in the
+/// rows past its end.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GrowthDiff {
+    pub rows: Vec<GrowthRow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrowthRow {
+    pub size_a: usize,
+    pub size_b: usize,
+    /// `size_b - size_a`.
+    pub delta: i64,
+}
+
+impl GrowthDiff {
+    pub fn new(sizes_a: &[usize], sizes_b: &[usize]) -> Self {
+        let len = sizes_a.len().max(sizes_b.len());
+        let at = |sizes: &[usize], i: usize| sizes.get(i).or(sizes.last()).copied().unwrap_or(0);
+        let rows = (0..len)
+            .map(|i| {
+                let (size_a, size_b) = (at(sizes_a, i), at(sizes_b, i));
+                GrowthRow {
+                    size_a,
+                    size_b,
+                    delta: size_b as i64 - size_a as i64,
+                }
+            })
+            .collect();
+        Self { rows }
+    }
+
+    /// A table with a row per iteration, like `format_table`.
+    pub fn to_table(&self) -> String {
+        let mut table = format!("{:>9} {:>9} {:>9} {:>9}\n", "iteration", "a", "b", "delta");
+        for (i, row) in self.rows.iter().enumerate() {
+            table.push_str(&format!(
+                "{:>9} {:>9} {:>9} {:>+9}\n",
+                i, row.size_a, row.size_b, row.delta
+            ));
+        }
+        table
+    }
+}
+
+/// One stage of `Bench::staged_run`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StageReport {
+    pub rules: usize,
+    pub stop_reason: String,
+    pub iterations: usize,
+    /// E-graph size when the stage started and when it stopped.
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    pub classes_before: usize,
+    pub classes_after: usize,
+    /// Total runner time in seconds.
+    pub total_time: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::lambda_bench2;
+    use crate::RunConfig;
+
+    /// A markdown table has the header, the `---` separator GitHub needs,
+    /// and a row per report.
+    #[test]
+    fn markdown_table() {
+        let report = BenchReport {
+            name: "lambda2".into(),
+            ..Default::default()
+        };
+        let table = format_markdown(&[report.clone(), BenchReport::timed_out("slow")]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("| name | saturated |"));
+        assert_eq!(
+            lines[1],
+            "| --- | --- | --- | --- | --- | --- | --- | --- |"
+        );
+        assert!(lines[2].starts_with("| lambda2 | no |"));
+        assert!(lines[3].starts_with("| slow | timed out |"));
+        assert_eq!(report.to_markdown(), lines[..3].join("\n") + "\n");
+    }
+
+    /// A report with its per-iteration series and rule stats filled in
+    /// reads back from JSON unchanged.
+    #[test]
+    fn json_roundtrip() {
+        let config = RunConfig {
+            track_convergence: true,
+            track_matches: true,
+            time_rules: true,
+            ..Default::default()
+        };
+        let report = lambda_bench2().run(&config).unwrap();
+        report.check_roundtrip().unwrap();
+    }
+
+    /// A run that took half the baseline's time is a 2x speedup.
+    #[test]
+    fn speedup_vs() {
+        let report = |total_time| BenchReport {
+            total_time,
+            ..Default::default()
+        };
+        assert_eq!(report(1.0).speedup_vs(&report(2.0)), 2.0);
+        assert_eq!(report(2.0).speedup_vs(&report(1.0)), 0.5);
+    }
+
+    /// Appending twice writes the header once, then a row per report.
+    #[test]
+    fn append_csv_header_once() {
+        let path = std::env::temp_dir().join(format!("append-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let report = BenchReport {
+            name: "lambda2".into(),
+            ..Default::default()
+        };
+        append_csv(&report, &path, "abc").unwrap();
+        append_csv(&report, &path, "def").unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", csv);
+        assert!(lines[0].starts_with("run_id,name,"), "{}", lines[0]);
+        assert!(lines[1].starts_with("abc,lambda2,"), "{}", lines[1]);
+        assert!(lines[2].starts_with("def,lambda2,"), "{}", lines[2]);
+    }
+}
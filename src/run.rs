@@ -0,0 +1,1279 @@
+use crate::*;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The message of egg's `StopReason::Other` when a run stops because
+/// `stop_when_pattern` matched.
+pub const PATTERN_FOUND: &str = "PatternFound";
+
+/// The message of egg's `StopReason::Other` when `stall_limit` stops a run.
+pub const STALLED: &str = "Stalled";
+
+/// The message of egg's `StopReason::Other` when
+/// `StopPolicy::DiminishingReturns` stops a run.
+pub const DIMINISHING_RETURNS: &str = "DiminishingReturns";
+
+/// When to stop a run short of egg's limits, judging by how its result is
+/// coming along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopPolicy {
+    /// Stop once the best `AstSize` over the roots has dropped by less than
+    /// `min_delta` over the last `window` iterations. This extracts before
+    /// every iteration, as `track_convergence` does, so it costs as much.
+    DiminishingReturns { window: usize, min_delta: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    pub node_limit: usize,
+    pub iter_limit: usize,
+    pub time_limit: Duration,
+    /// Extract the roots after every iteration to record how the best cost
+    /// evolves. This is expensive, so it's off by default.
+    pub track_convergence: bool,
+    pub cost_metric: CostMetric,
+    /// Only run the rules with these names.
+    pub only_rules: Option<Vec<String>>,
+    /// Don't run the rules with these names.
+    pub skip_rules: Vec<String>,
+    /// Shuffle the rules with this seed before running; see `shuffle_rules`.
+    pub shuffle_rules_seed: Option<u64>,
+    /// Fail the run with `Error::NotSaturated` unless it stops because it
+    /// saturated. Small benches that should saturate hitting a limit
+    /// instead usually means a rule loops.
+    pub assert_saturates: bool,
+    /// Fail the run with `Error::TooFewIterations` if it stops after fewer
+    /// iterations than this, as when a rule set accidentally leaves a bench
+    /// with nothing to do.
+    pub min_iterations: Option<usize>,
+    /// Count every rule's matches at each iteration. This searches with
+    /// every rule a second time, so it's off by default.
+    pub track_matches: bool,
+    /// Time every rule's apply phase at each iteration, by applying its
+    /// matches to a scratch copy of the e-graph. Off by default, as it's
+    /// about as slow as another run.
+    pub time_rules: bool,
+    /// Warn about any rule taking more than this fraction of the apply time.
+    /// Implies `time_rules`.
+    pub warn_slow_rule: Option<f64>,
+    /// Don't saturate, just time each rule's search on the initial e-graph.
+    pub search_only: bool,
+    /// Extract from a copy of the final e-graph with only the e-classes
+    /// reachable from the roots.
+    pub prune_unreachable: bool,
+    /// How to extract `best_cost` and `results`.
+    pub extract_mode: ExtractMode,
+    /// Record every union the rules make, by replaying each iteration's
+    /// matches one at a time on a scratch copy of the e-graph. This uses
+    /// egg's `SimpleScheduler` so that the replay sees the same matches as
+    /// the runner.
+    pub trace: bool,
+    /// Report this many pairs of rules whose applications in sequence made
+    /// the most new e-nodes. Like `trace`, this replays every match on a
+    /// scratch copy of the e-graph, and it is approximate: see `RulePair`.
+    pub rule_pairs: Option<usize>,
+    /// Write each bench's files under this directory, named
+    /// `<bench name>.<ext>`: a CSV of the iterations from `saturate`, and
+    /// whatever else the caller adds with `artifact_path`.
+    pub artifact_dir: Option<PathBuf>,
+    /// Stop as soon as this pattern matches some e-class, with the stop
+    /// reason `PATTERN_FOUND`, rather than running to saturation. It's
+    /// checked before each iteration and once more after the run.
+    pub stop_when_pattern: Option<String>,
+    /// Stop with the stop reason `STALLED` after this many iterations in a
+    /// row that neither added e-nodes nor applied a rule. egg only declares
+    /// saturation when no rule is banned, so a scheduler that keeps banning
+    /// rules can otherwise iterate to the limit without doing anything.
+    pub stall_limit: Option<usize>,
+    /// Give egg's `BackoffScheduler` this initial match limit, in place of
+    /// its default. Ignored with `trace` or `rule_pairs`, which use egg's
+    /// `SimpleScheduler`.
+    pub match_limit: Option<usize>,
+    /// Stop when this says the run isn't getting anywhere, with the
+    /// policy's stop reason. See `StopPolicy`.
+    pub stop_policy: Option<StopPolicy>,
+    /// Write the e-graph after each iteration to `step<i>.dot` in this
+    /// directory, from `step0.dot` for the start to one per iteration run,
+    /// for stitching into an animation. Every bench writes to the same
+    /// files, so animate one at a time.
+    pub animate_dir: Option<PathBuf>,
+    /// Frames of e-graphs with more e-nodes than this are written as a
+    /// placeholder saying so, as Graphviz can't lay out big e-graphs.
+    pub animate_max_nodes: usize,
+    /// How many distinct e-nodes the start expressions are expected to add.
+    /// egg can't pre-size an e-graph, so this only pre-sizes the map the
+    /// start expressions are added through, which shares their common
+    /// subterms; the e-graph comes out the same either way.
+    pub initial_capacity: Option<usize>,
+    /// Profile each run, writing `<bench name>.folded` to `artifact_dir`,
+    /// or the working directory without one.
+    #[cfg(feature = "profile")]
+    pub profile: bool,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            node_limit: 10_000,
+            iter_limit: 30,
+            time_limit: Duration::from_secs(5),
+            track_convergence: false,
+            cost_metric: CostMetric::AstSize,
+            only_rules: None,
+            skip_rules: vec![],
+            shuffle_rules_seed: None,
+            assert_saturates: false,
+            min_iterations: None,
+            track_matches: false,
+            time_rules: false,
+            warn_slow_rule: None,
+            search_only: false,
+            prune_unreachable: false,
+            extract_mode: ExtractMode::Full,
+            trace: false,
+            rule_pairs: None,
+            artifact_dir: None,
+            stop_when_pattern: None,
+            stall_limit: None,
+            match_limit: None,
+            stop_policy: None,
+            animate_dir: None,
+            animate_max_nodes: 200,
+            initial_capacity: None,
+            #[cfg(feature = "profile")]
+            profile: false,
+        }
+    }
+}
+
+impl RunConfig {
+    /// Where to write the `ext` file for the bench `name`, creating
+    /// `artifact_dir` if it's missing. `None` without an `artifact_dir`.
+    pub fn artifact_path(&self, name: &str, ext: &str) -> Result<Option<PathBuf>, Error> {
+        let dir = match &self.artifact_dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        std::fs::create_dir_all(dir)?;
+        Ok(Some(dir.join(format!("{}.{}", name, ext))))
+    }
+}
+
+/// A row of the iterations CSV in `artifact_dir`.
+#[derive(Serialize)]
+struct IterationRow {
+    iteration: usize,
+    nodes: usize,
+    classes: usize,
+    applied: usize,
+    search_time: f64,
+    apply_time: f64,
+    rebuild_time: f64,
+    total_time: f64,
+}
+
+fn write_iterations<L: Language, A: Analysis<L>>(
+    path: &Path,
+    runner: &Runner<L, A>,
+) -> Result<(), Error> {
+    let mut wtr = csv::Writer::from_path(path).map_err(std::io::Error::from)?;
+    for (i, iteration) in runner.iterations.iter().enumerate() {
+        wtr.serialize(IterationRow {
+            iteration: i,
+            nodes: iteration.egraph_nodes,
+            classes: iteration.egraph_classes,
+            applied: iteration.applied.values().sum(),
+            search_time: iteration.search_time,
+            apply_time: iteration.apply_time,
+            rebuild_time: iteration.rebuild_time,
+            total_time: iteration.total_time,
+        })
+        .map_err(std::io::Error::from)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Writes `egraph` to `step<step>.dot` in `dir`, or a placeholder if it has
+/// more than `max_nodes` e-nodes.
+fn write_frame<L, A>(
+    dir: &Path,
+    step: usize,
+    egraph: &EGraph<L, A>,
+    max_nodes: usize,
+) -> Result<(), Error>
+where
+    L: Language + std::fmt::Display,
+    A: Analysis<L>,
+{
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("step{}.dot", step));
+    let nodes = egraph.total_number_of_nodes();
+    let written = if nodes > max_nodes {
+        std::fs::write(
+            &path,
+            format!(
+                "digraph egraph {{\n  label=\"step {}: {} e-nodes, over the cap of {}\"\n}}\n",
+                step, nodes, max_nodes
+            ),
+        )
+    } else {
+        egraph.dot().to_dot(&path)
+    };
+    Ok(written?)
+}
+
+/// Sum of the best `AstSize` over all `roots`.
+pub fn best_cost<L: Language, A: Analysis<L>>(egraph: &EGraph<L, A>, roots: &[Id]) -> usize {
+    let extractor = Extractor::new(egraph, AstSize);
+    roots.iter().map(|&root| extractor.find_best(root).0).sum()
+}
+
+/// Runs `f` on its own thread, returning `None` if it takes longer than
+/// `timeout`. The runner has its own limits, so this only guards against
+/// pathological rules or hooks. A thread that times out can't be killed, it
+/// is just left behind. If `f` panics, the panic carries on in the caller,
+/// rather than passing for a timeout.
+pub fn with_timeout<T, F>(timeout: Duration, f: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || sender.send(f()).unwrap_or_default());
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Some(result),
+        Err(mpsc::RecvTimeoutError::Timeout) => None,
+        // the sender was dropped without sending, so `f` panicked
+        Err(mpsc::RecvTimeoutError::Disconnected) => match handle.join() {
+            Err(panic) => std::panic::resume_unwind(panic),
+            Ok(()) => unreachable!("the thread finished without sending"),
+        },
+    }
+}
+
+/// The rules whose match count went over `match_limit` in some iteration.
+/// `BackoffScheduler` bans a rule the first time its matches exceed its
+/// `initial_match_limit`, so this shows which rules a limit would ban.
+pub fn rules_over_match_limit(
+    matches: &BTreeMap<String, Vec<usize>>,
+    match_limit: usize,
+) -> Vec<String> {
+    matches
+        .iter()
+        .filter(|(_, counts)| counts.iter().any(|&n| n > match_limit))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Whether each of the last `limit` iterations of `runner` applied no rules
+/// and left the e-graph the size it found it. egg doesn't record the unions
+/// an iteration made, so applying nothing stands in for making no unions.
+/// Sizes are `total_size`, as the runner records in `egraph_nodes`.
+fn stalled<L: Language, A: Analysis<L>>(runner: &Runner<L, A>, limit: usize) -> bool {
+    let iterations = &runner.iterations;
+    if limit == 0 || iterations.len() < limit {
+        return false;
+    }
+    let sizes_after = iterations
+        .iter()
+        .skip(1)
+        .map(|i| i.egraph_nodes)
+        .chain(std::iter::once(runner.egraph.total_size()));
+    iterations
+        .iter()
+        .zip(sizes_after)
+        .skip(iterations.len() - limit)
+        .all(|(i, after)| i.applied.values().all(|&n| n == 0) && i.egraph_nodes == after)
+}
+
+/// A warning for each rule taking more than `fraction` of the total time in
+/// `apply_time`.
+pub fn slow_rule_warnings(apply_time: &BTreeMap<String, f64>, fraction: f64) -> Vec<String> {
+    let total: f64 = apply_time.values().sum();
+    apply_time
+        .iter()
+        .filter(|(_, &time)| total > 0.0 && time / total > fraction)
+        .map(|(name, &time)| {
+            let percent = 100.0 * time / total;
+            format!("rule {} took {:.0}% of the apply time", name, percent)
+        })
+        .collect()
+}
+
+/// Runs `runner`, under the profiler if `config.profile` is set.
+#[cfg(feature = "profile")]
+fn run_rules<L: Language, A: Analysis<L>>(
+    config: &RunConfig,
+    name: &str,
+    runner: Runner<L, A>,
+    rules: &[Rewrite<L, A>],
+) -> Result<Runner<L, A>, Error> {
+    if !config.profile {
+        return Ok(runner.run(rules));
+    }
+    let path = config
+        .artifact_path(name, "folded")?
+        .unwrap_or_else(|| format!("{}.folded", name).into());
+    profiled(&path, || runner.run(rules))
+}
+
+#[cfg(not(feature = "profile"))]
+fn run_rules<L: Language, A: Analysis<L>>(
+    _config: &RunConfig,
+    _name: &str,
+    runner: Runner<L, A>,
+    rules: &[Rewrite<L, A>],
+) -> Result<Runner<L, A>, Error> {
+    Ok(runner.run(rules))
+}
+
+/// Per-iteration measurements, recorded by a hook.
+#[derive(Debug, Clone, Default)]
+struct Series {
+    convergence: Vec<usize>,
+    root_nodes: Vec<usize>,
+    matches: BTreeMap<String, Vec<usize>>,
+    apply_time: BTreeMap<String, f64>,
+    search_time: BTreeMap<String, f64>,
+    trace: Vec<TraceEntry>,
+    /// The rule that last added e-nodes to each e-class.
+    class_origin: HashMap<Id, String>,
+    rule_pairs: BTreeMap<(String, String), usize>,
+}
+
+impl Series {
+    fn record<L: Language, A: Analysis<L>>(
+        &mut self,
+        config: &RunConfig,
+        egraph: &EGraph<L, A>,
+        roots: &[Id],
+    ) {
+        if config.track_convergence {
+            self.convergence.push(best_cost(egraph, roots));
+        }
+        let root_nodes = roots.iter().map(|&root| egraph[root].nodes.len());
+        self.root_nodes.push(root_nodes.sum());
+    }
+
+    fn time_searches<L: Language, A: Analysis<L>>(
+        &mut self,
+        rules: &[Rewrite<L, A>],
+        egraph: &EGraph<L, A>,
+    ) {
+        for rule in rules {
+            let start = Instant::now();
+            rule.search(egraph);
+            let time = start.elapsed().as_secs_f64();
+            self.search_time.insert(rule.name().to_string(), time);
+        }
+    }
+
+    fn record_rules<L: Language, A: Analysis<L> + Clone>(
+        &mut self,
+        config: &RunConfig,
+        rules: &[Rewrite<L, A>],
+        egraph: &EGraph<L, A>,
+        iteration: usize,
+    ) where
+        A::Data: Clone,
+    {
+        let time_rules = config.time_rules || config.warn_slow_rule.is_some();
+        let rule_pairs = config.rule_pairs.is_some();
+        let one_by_one = config.trace || rule_pairs;
+        if !(config.track_matches || time_rules || one_by_one) {
+            return;
+        }
+        if rule_pairs {
+            let origins = std::mem::take(&mut self.class_origin);
+            self.class_origin = origins
+                .into_iter()
+                .map(|(id, rule)| (egraph.find(id), rule))
+                .collect();
+        }
+        // like the runner, search everything before applying anything
+        let mut scratch = if time_rules || one_by_one {
+            Some(egraph.clone())
+        } else {
+            None
+        };
+        for rule in rules {
+            let name = rule.name().to_string();
+            let matches = rule.search(egraph);
+            if config.track_matches {
+                let n = matches.iter().map(|m| m.substs.len()).sum();
+                self.matches.entry(name.clone()).or_default().push(n);
+            }
+            if let Some(scratch) = &mut scratch {
+                let start = Instant::now();
+                let mut applied = vec![];
+                if one_by_one {
+                    for m in &matches {
+                        let nodes_before = scratch.total_number_of_nodes();
+                        let ids = rule.apply(scratch, std::slice::from_ref(m));
+                        let added = scratch.total_number_of_nodes() - nodes_before;
+                        if rule_pairs && added > 0 {
+                            let eclass = egraph.find(m.eclass);
+                            if let Some(first) = self.class_origin.get(&eclass) {
+                                let pair = (first.clone(), name.clone());
+                                *self.rule_pairs.entry(pair).or_default() += added;
+                            }
+                            self.class_origin.insert(eclass, name.clone());
+                        }
+                        if config.trace {
+                            applied.extend(ids.into_iter().map(|id| (m.eclass, id)));
+                        }
+                    }
+                } else {
+                    rule.apply(scratch, &matches);
+                }
+                let time = start.elapsed().as_secs_f64();
+                self.trace
+                    .extend(applied.into_iter().map(|(eclass, id)| TraceEntry {
+                        iteration,
+                        rule: name.clone(),
+                        eclass: eclass.into(),
+                        result: id.into(),
+                    }));
+                *self.apply_time.entry(name).or_default() += time;
+            }
+        }
+    }
+}
+
+impl<L, A> Bench<L, A>
+where
+    L: Language + FromOp + std::fmt::Display + 'static,
+    A: Analysis<L> + Default + Clone,
+    A::Data: Clone,
+{
+    /// Checks everything `run` would check before saturating, without
+    /// saturating: that the start expressions, seeds, assumptions, and
+    /// patterns parse, that the rule names are unique, and that `config`
+    /// only names rules the bench has. Lambda benches can also be checked
+    /// with `check_closed`.
+    pub fn dry_run(&self, config: &RunConfig) -> Result<(), Error> {
+        self.validate()?;
+        filter_rules(
+            &self.rules,
+            config.only_rules.as_deref(),
+            &config.skip_rules,
+        )?;
+        let seeds = self
+            .seed_equivalences
+            .iter()
+            .chain(&self.assumptions)
+            .flat_map(|(a, b)| vec![a, b]);
+        for expr in self.start_exprs.iter().chain(seeds) {
+            expr.parse::<RecExpr<L>>()
+                .map_err(|e| Error::Parse(format!("{}: {}", expr, e)))?;
+        }
+        for pat in self.bench_pats().iter().chain(&config.stop_when_pattern) {
+            pat.parse::<Pattern<L>>()
+                .map_err(|e| Error::Parse(format!("{}: {}", pat, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Saturates the benchmark once under `config` and reports on the run.
+    pub fn run(&self, config: &RunConfig) -> Result<BenchReport, Error> {
+        Ok(self.saturate(config)?.1)
+    }
+
+    /// Like `run`, but also hands back the runner with the final e-graph.
+    pub fn saturate(&self, config: &RunConfig) -> Result<(Runner<L, A>, BenchReport), Error> {
+        self.validate()?;
+        let mut rules = filter_rules(
+            &self.rules,
+            config.only_rules.as_deref(),
+            &config.skip_rules,
+        )?;
+        if let Some(seed) = config.shuffle_rules_seed {
+            shuffle_rules(&mut rules, seed);
+        }
+        let mut egraph: EGraph<L, A> = EGraph::new(self.analysis.clone());
+        egraph.strategy = Strategy::GenericJoin;
+        let mut runner: Runner<L, A> = Runner::default()
+            .with_egraph(egraph)
+            .with_node_limit(config.node_limit)
+            .with_iter_limit(config.iter_limit)
+            .with_time_limit(config.time_limit);
+        if config.trace || config.rule_pairs.is_some() {
+            runner = runner.with_scheduler(SimpleScheduler);
+        } else if let Some(limit) = config.match_limit {
+            runner =
+                runner.with_scheduler(BackoffScheduler::default().with_initial_match_limit(limit));
+        }
+        let parse_start = Instant::now();
+        let exprs: Vec<RecExpr<L>> = self
+            .start_exprs
+            .iter()
+            .map(|expr| {
+                expr.parse()
+                    .map_err(|e| Error::Parse(format!("{}: {}", expr, e)))
+            })
+            .collect::<Result<_, _>>()?;
+        let parse_time = parse_start.elapsed().as_secs_f64();
+        let mut added: HashMap<L, Id> =
+            HashMap::with_capacity(config.initial_capacity.unwrap_or(0));
+        for expr in &exprs {
+            let mut ids: Vec<Id> = vec![];
+            for node in expr.as_ref() {
+                let node = node.clone().map_children(|c| ids[usize::from(c)]);
+                let egraph = &mut runner.egraph;
+                let id = *added
+                    .entry(node.clone())
+                    .or_insert_with(|| egraph.add(node));
+                ids.push(id);
+            }
+            runner.roots.push(*ids.last().unwrap());
+        }
+        let seeds = self.add_seeds(&mut runner.egraph)?;
+
+        // hooks run at the start of each iteration, so this sees the
+        // e-graph before the first iteration and after all but the last
+        let series = Rc::new(RefCell::new(Series::default()));
+        {
+            let series = series.clone();
+            let config = config.clone();
+            let rules = rules.clone();
+            runner = runner.with_hook(move |runner| {
+                let mut series = series.borrow_mut();
+                series.record(&config, &runner.egraph, &runner.roots);
+                let iteration = runner.iterations.len();
+                series.record_rules(&config, &rules, &runner.egraph, iteration);
+                Ok(())
+            });
+        }
+
+        // the frame a stopping hook cuts off is written after the run
+        if let Some(dir) = config.animate_dir.clone() {
+            let max_nodes = config.animate_max_nodes;
+            runner = runner.with_hook(move |runner| {
+                write_frame(&dir, runner.iterations.len(), &runner.egraph, max_nodes)
+                    .map_err(|e| e.to_string())
+            });
+        }
+
+        let pattern_found_at = Rc::new(RefCell::new(None));
+        let target = match &config.stop_when_pattern {
+            Some(pat) => Some(
+                pat.parse::<Pattern<L>>()
+                    .map_err(|e| Error::Parse(format!("{}: {}", pat, e)))?,
+            ),
+            None => None,
+        };
+        if let Some(target) = target.clone() {
+            let pattern_found_at = pattern_found_at.clone();
+            runner = runner.with_hook(move |runner| {
+                if target.search(&runner.egraph).is_empty() {
+                    Ok(())
+                } else {
+                    *pattern_found_at.borrow_mut() = Some(runner.iterations.len());
+                    Err(PATTERN_FOUND.into())
+                }
+            });
+        }
+
+        if let Some(limit) = config.stall_limit {
+            runner = runner.with_hook(move |runner| {
+                if stalled(runner, limit) {
+                    Err(STALLED.into())
+                } else {
+                    Ok(())
+                }
+            });
+        }
+
+        if let Some(StopPolicy::DiminishingReturns { window, min_delta }) = config.stop_policy {
+            let mut costs = vec![];
+            runner = runner.with_hook(move |runner| {
+                costs.push(best_cost(&runner.egraph, &runner.roots));
+                let n = costs.len();
+                // the best cost only falls as the e-graph grows, but a rise
+                // counts as no progress rather than underflowing
+                if window > 0
+                    && n > window
+                    && (costs[n - 1 - window].saturating_sub(costs[n - 1]) as u64) < min_delta
+                {
+                    Err(DIMINISHING_RETURNS.into())
+                } else {
+                    Ok(())
+                }
+            });
+        }
+
+        let runner = if config.search_only {
+            series.borrow_mut().time_searches(&rules, &runner.egraph);
+            runner
+        } else {
+            run_rules(config, &self.name, runner, &rules)?
+        };
+        series
+            .borrow_mut()
+            .record(config, &runner.egraph, &runner.roots);
+        let mut pattern_found_at = *pattern_found_at.borrow();
+        if let Some(target) = &target {
+            if pattern_found_at.is_none() && !target.search(&runner.egraph).is_empty() {
+                pattern_found_at = Some(runner.iterations.len());
+            }
+        }
+        if let Some(path) = config.artifact_path(&self.name, "csv")? {
+            write_iterations(&path, &runner)?;
+        }
+        if let Some(dir) = &config.animate_dir {
+            write_frame(
+                dir,
+                runner.iterations.len(),
+                &runner.egraph,
+                config.animate_max_nodes,
+            )?;
+        }
+        // warnings go in the report rather than straight to stdout, so runs
+        // on different threads don't interleave
+        let mut warnings: Vec<String> = self
+            .failed_seeds(&runner.egraph, &seeds)
+            .into_iter()
+            .map(|(a, b)| format!("seed equivalence does not hold: {} = {}", a, b))
+            .collect();
+
+        if config.assert_saturates {
+            match &runner.stop_reason {
+                Some(StopReason::Saturated) => (),
+                reason => {
+                    return Err(Error::NotSaturated {
+                        bench: self.name.clone(),
+                        stop_reason: format!("{:?}", reason),
+                    })
+                }
+            }
+        }
+        if let Some(min) = config.min_iterations {
+            let iterations = runner.iterations.len();
+            if iterations < min {
+                return Err(Error::TooFewIterations {
+                    bench: self.name.clone(),
+                    iterations,
+                    min,
+                });
+            }
+        }
+
+        let series = series.borrow().clone();
+        let mut rule_applications = BTreeMap::new();
+        for iteration in &runner.iterations {
+            for (name, n) in &iteration.applied {
+                *rule_applications.entry(name.to_string()).or_default() += n;
+            }
+        }
+        if let Some(fraction) = config.warn_slow_rule {
+            warnings.extend(slow_rule_warnings(&series.apply_time, fraction));
+        }
+        let mut rule_pairs: Vec<RulePair> = series
+            .rule_pairs
+            .iter()
+            .map(|((first, second), &nodes)| RulePair {
+                first: first.clone(),
+                second: second.clone(),
+                nodes,
+            })
+            .collect();
+        rule_pairs.sort_by(|a, b| b.nodes.cmp(&a.nodes));
+        rule_pairs.truncate(config.rule_pairs.unwrap_or(0));
+        let pattern_stats: Vec<PatternStats> = self
+            .bench_pats()
+            .iter()
+            .zip(self.patterns()?)
+            .map(|(source, pat)| {
+                let matches = pat.search(&runner.egraph);
+                PatternStats {
+                    pattern: source.clone(),
+                    candidates: pattern_candidates(&runner.egraph, pat),
+                    matched_classes: matches.len(),
+                    matches: matches.iter().map(|m| m.substs.len()).sum(),
+                }
+            })
+            .collect();
+        let zero_match_pats = pattern_stats
+            .iter()
+            .filter(|stats| stats.matches == 0)
+            .map(|stats| stats.pattern.clone())
+            .collect();
+        // leave out roots the extractor would panic on, rather than give up
+        // on the whole report
+        let stuck = unextractable_classes(&runner.egraph);
+        let mut extractable_roots = vec![];
+        let mut input_size = 0;
+        for (&root, expr) in runner.roots.iter().zip(&exprs) {
+            if stuck.contains(&runner.egraph.find(root)) {
+                warnings.push(format!(
+                    "root e-class {} has no finite term to extract",
+                    root
+                ));
+            } else {
+                extractable_roots.push(root);
+                input_size += AstSize.cost_rec(expr);
+            }
+        }
+        let pruned = if config.prune_unreachable {
+            Some(prune_unreachable(&runner.egraph, &extractable_roots))
+        } else {
+            None
+        };
+        let (egraph, roots, pruned_classes) = match &pruned {
+            Some((egraph, roots, n)) => (egraph, roots, *n),
+            None => (&runner.egraph, &extractable_roots, 0),
+        };
+        let extraction_start = Instant::now();
+        let (best, peak_working_set): (Vec<(usize, RecExpr<L>)>, _) = match config.extract_mode {
+            ExtractMode::Full => {
+                let extractor = Extractor::new(egraph, AstSize);
+                let best = roots
+                    .iter()
+                    .map(|&root| extractor.find_best(root))
+                    .collect();
+                (best, None)
+            }
+            ExtractMode::Streaming { max_working_set } => {
+                let mut peak = 0;
+                let mut best = vec![];
+                for &root in roots {
+                    match streaming_extract(egraph, root, max_working_set) {
+                        Some(streamed) => {
+                            peak = peak.max(streamed.peak_working_set);
+                            best.push((streamed.cost, streamed.expr));
+                        }
+                        None => {
+                            warnings.push(format!(
+                                "streaming extraction of root e-class {} dropped every \
+                                 term, so it was extracted in full",
+                                root
+                            ));
+                            best.push(Extractor::new(egraph, AstSize).find_best(root));
+                        }
+                    }
+                }
+                (best, Some(peak))
+            }
+            ExtractMode::TieBreak(nesting) => {
+                let extractor = Extractor::new(egraph, TieBreak(nesting));
+                let best = roots
+                    .iter()
+                    .map(|&root| {
+                        let (cost, expr) = extractor.find_best(root);
+                        (cost.size, expr)
+                    })
+                    .collect();
+                (best, None)
+            }
+        };
+        let extraction_time = extraction_start.elapsed().as_secs_f64();
+        let greedy_dag_cost = roots
+            .iter()
+            .map(|&root| greedy_dag_size(egraph, root))
+            .sum();
+        let optimal_dag_cost = match config.cost_metric {
+            CostMetric::AstSize => None,
+            #[cfg(feature = "lp")]
+            CostMetric::OptimalDag => {
+                let mut cost = 0;
+                for &root in roots.iter() {
+                    cost += optimal_dag_extract(egraph, root)?.0;
+                }
+                Some(cost)
+            }
+        };
+        let best_cost: usize = best.iter().map(|(cost, _)| cost).sum();
+        let result_converged_at = series.convergence.last().map(|last| {
+            let changed = series.convergence.iter().rposition(|cost| cost != last);
+            changed.map_or(0, |i| i + 1)
+        });
+        let report = BenchReport {
+            name: self.name.clone(),
+            stop_reason: format!("{:?}", runner.stop_reason),
+            saturated: matches!(runner.stop_reason, Some(StopReason::Saturated)),
+            iterations: runner.iterations.len(),
+            nodes: runner.egraph.total_number_of_nodes(),
+            classes: runner.egraph.number_of_classes(),
+            total_time: runner.iterations.iter().map(|i| i.total_time).sum(),
+            search_time: runner.iterations.iter().map(|i| i.search_time).sum(),
+            apply_time: runner.iterations.iter().map(|i| i.apply_time).sum(),
+            rebuild_time: runner.iterations.iter().map(|i| i.rebuild_time).sum(),
+            parse_time,
+            extraction_time,
+            peak_working_set,
+            convergence: series.convergence,
+            result_converged_at,
+            root_nodes: series.root_nodes,
+            rule_applications,
+            rule_matches: series.matches,
+            rule_apply_time: series.apply_time,
+            rule_search_time: series.search_time,
+            trace: series.trace,
+            rule_pairs,
+            zero_match_pats,
+            pattern_stats,
+            warnings,
+            best_cost,
+            simplification_ratio: best_cost as f64 / input_size as f64,
+            results: best.iter().map(|(_, expr)| expr.pretty(80)).collect(),
+            pruned_classes,
+            greedy_dag_cost,
+            optimal_dag_cost,
+            timed_out: false,
+            pattern_found_at,
+            analysis_stats: None,
+            operator_counts: BTreeMap::new(),
+        };
+        Ok((runner, report))
+    }
+
+    /// Saturates once with `set_a` and once with `set_b`, in place of the
+    /// bench's own rules, and lines up the e-graph sizes iteration by
+    /// iteration.
+    pub fn compare_growth(
+        &self,
+        config: &RunConfig,
+        set_a: Vec<Rewrite<L, A>>,
+        set_b: Vec<Rewrite<L, A>>,
+    ) -> Result<GrowthDiff, Error> {
+        let sizes = |rules| -> Result<Vec<usize>, Error> {
+            let (runner, _) = self.clone().with_rules(rules).saturate(config)?;
+            let mut sizes: Vec<usize> = runner.iterations.iter().map(|i| i.egraph_nodes).collect();
+            sizes.push(runner.egraph.total_number_of_nodes());
+            Ok(sizes)
+        };
+        Ok(GrowthDiff::new(&sizes(set_a)?, &sizes(set_b)?))
+    }
+
+    /// Saturates with each rule set in turn, on the same e-graph, ignoring the
+    /// bench's own rules. Each stage gets the limits in `config`. This can
+    /// differ from one run with all the rules: earlier stages' rules never
+    /// see what later stages add, and each stage's limits are counted from
+    /// its own start.
+    pub fn staged_run(
+        &self,
+        config: &RunConfig,
+        stages: Vec<Vec<Rewrite<L, A>>>,
+    ) -> Result<(EGraph<L, A>, Vec<StageReport>), Error> {
+        let mut egraph: EGraph<L, A> = EGraph::new(self.analysis.clone());
+        egraph.strategy = Strategy::GenericJoin;
+        for expr in &self.start_exprs {
+            let expr: RecExpr<L> = expr
+                .parse()
+                .map_err(|e| Error::Parse(format!("{}: {}", expr, e)))?;
+            egraph.add_expr(&expr);
+        }
+        egraph.rebuild();
+        self.add_seeds(&mut egraph)?;
+
+        let mut reports = vec![];
+        for rules in stages {
+            let nodes_before = egraph.total_number_of_nodes();
+            let classes_before = egraph.number_of_classes();
+            let runner: Runner<L, A> = Runner::default()
+                .with_egraph(egraph)
+                .with_node_limit(config.node_limit)
+                .with_iter_limit(config.iter_limit)
+                .with_time_limit(config.time_limit)
+                .run(&rules);
+            egraph = runner.egraph;
+            reports.push(StageReport {
+                rules: rules.len(),
+                stop_reason: format!("{:?}", runner.stop_reason),
+                iterations: runner.iterations.len(),
+                nodes_before,
+                nodes_after: egraph.total_number_of_nodes(),
+                classes_before,
+                classes_after: egraph.number_of_classes(),
+                total_time: runner.iterations.iter().map(|i| i.total_time).sum(),
+            });
+        }
+        Ok((egraph, reports))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::{
+        lambda_bench0, lambda_bench1, lambda_bench2, lambda_bench_onestep, program_bench, rules,
+        rules_for, Lambda, LambdaAnalysis, RuleTag,
+    };
+
+    /// `search_only` times every rule's search on the initial e-graph and
+    /// leaves it as it was, with nothing applied or unioned.
+    #[test]
+    fn search_only_changes_nothing() {
+        let bench = lambda_bench2();
+        let mut egraph: EGraph<Lambda, LambdaAnalysis> = EGraph::new(bench.analysis.clone());
+        egraph.add_expr(&bench.start_exprs[0].parse().unwrap());
+        egraph.rebuild();
+        let config = RunConfig {
+            search_only: true,
+            ..Default::default()
+        };
+        let report = bench.run(&config).unwrap();
+        assert_eq!(report.iterations, 0);
+        assert_eq!(report.nodes, egraph.total_number_of_nodes());
+        assert_eq!(report.classes, egraph.number_of_classes());
+        assert_eq!(report.rule_search_time.len(), bench.rules.len());
+    }
+
+    /// `lambda_bench1` run with `stop_when_pattern` stops as soon as the
+    /// identity function `(lam i (var i))` appears, in fewer iterations than
+    /// a full run.
+    #[test]
+    fn stop_when_pattern() {
+        let full = lambda_bench1().run(&RunConfig::default()).unwrap();
+        let config = RunConfig {
+            stop_when_pattern: Some("(lam i (var i))".into()),
+            ..Default::default()
+        };
+        let stopped = lambda_bench1().run(&config).unwrap();
+        let at = stopped.pattern_found_at.expect("never found the pattern");
+        assert!(
+            at < full.iterations,
+            "found the pattern after {} iterations, but a full run takes {}",
+            at,
+            full.iterations
+        );
+    }
+
+    /// `stall_limit` stops `lambda_bench1` when a tiny match limit makes the
+    /// scheduler ban every rule that still has matches: those iterations
+    /// apply nothing, but egg won't call it saturated while rules are banned.
+    #[test]
+    fn stall_limit() {
+        let config = RunConfig {
+            match_limit: Some(1),
+            stall_limit: Some(2),
+            iter_limit: 100,
+            ..Default::default()
+        };
+        let report = lambda_bench1().run(&config).unwrap();
+        assert!(
+            report.stop_reason.contains(STALLED),
+            "{}",
+            report.stop_reason
+        );
+    }
+
+    /// `StopPolicy::DiminishingReturns` stops `lambda_bench2` once its best
+    /// cost has held still for three iterations, before the run without it
+    /// stops.
+    #[test]
+    fn diminishing_returns() {
+        let full = lambda_bench2().run(&RunConfig::default()).unwrap();
+        let config = RunConfig {
+            stop_policy: Some(StopPolicy::DiminishingReturns {
+                window: 3,
+                min_delta: 1,
+            }),
+            ..Default::default()
+        };
+        let report = lambda_bench2().run(&config).unwrap();
+        assert!(
+            report.stop_reason.contains(DIMINISHING_RETURNS),
+            "{}",
+            report.stop_reason
+        );
+        assert!(report.iterations < full.iterations);
+    }
+
+    /// The best cost after each iteration never goes up, as the e-graph only
+    /// gains terms, and ends at the reported best cost.
+    #[test]
+    fn convergence_non_increasing() {
+        let config = RunConfig {
+            track_convergence: true,
+            ..Default::default()
+        };
+        let report = lambda_bench2().run(&config).unwrap();
+        assert!(report.convergence.len() > 1);
+        assert!(
+            report.convergence.windows(2).all(|w| w[1] <= w[0]),
+            "{:?}",
+            report.convergence
+        );
+        assert_eq!(report.convergence.last(), Some(&report.best_cost));
+    }
+
+    /// A run of `lambda_bench2` tracking convergence says when its best cost
+    /// settled, no later than the iterations run.
+    #[test]
+    fn result_converged_at() {
+        let config = RunConfig {
+            track_convergence: true,
+            ..Default::default()
+        };
+        let report = lambda_bench2().run(&config).unwrap();
+        let at = report
+            .result_converged_at
+            .expect("result_converged_at is missing");
+        assert!(at <= report.iterations);
+    }
+
+    /// `lambda_bench2`'s simplification ratio is below 1, since fib 4
+    /// simplifies to 3.
+    #[test]
+    fn simplification_ratio() {
+        let report = lambda_bench2().run(&RunConfig::default()).unwrap();
+        let ratio = report.simplification_ratio;
+        assert!(ratio > 0.0 && ratio < 1.0, "{}", ratio);
+    }
+
+    /// Animating `lambda_bench_onestep` writes `step0.dot` through one frame
+    /// per iteration, and nothing else, to a fresh directory.
+    #[test]
+    fn animate() {
+        let dir = std::env::temp_dir().join(format!("egg-bench-animate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = RunConfig {
+            animate_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let report = lambda_bench_onestep().run(&config).unwrap();
+        let mut frames: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        frames.sort();
+        let mut expected: Vec<String> = (0..=report.iterations)
+            .map(|i| format!("step{}.dot", i))
+            .collect();
+        expected.sort();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(frames, expected);
+    }
+
+    /// Every pattern's match is at one of its candidate e-classes, so there
+    /// are at least as many candidates as matched e-classes.
+    #[test]
+    fn pattern_candidates_bound_matches() {
+        let report = lambda_bench0().run(&RunConfig::default()).unwrap();
+        assert!(!report.pattern_stats.is_empty());
+        for stats in &report.pattern_stats {
+            assert!(
+                stats.candidates >= stats.matched_classes,
+                "{}: {} candidates, {} matched",
+                stats.pattern,
+                stats.candidates,
+                stats.matched_classes
+            );
+        }
+        assert!(report.pattern_stats.iter().any(|stats| stats.matches > 0));
+    }
+
+    /// A bench with no rules stops after one iteration, so asking for two
+    /// fails the run.
+    #[test]
+    fn min_iterations() {
+        let config = RunConfig {
+            min_iterations: Some(2),
+            ..Default::default()
+        };
+        match lambda_bench0().with_rules(vec![]).run(&config) {
+            Err(Error::TooFewIterations {
+                iterations, min, ..
+            }) => {
+                assert!(iterations < 2);
+                assert_eq!(min, 2);
+            }
+            Err(e) => panic!("wrong error: {}", e),
+            Ok(_) => panic!("a no-op run passed min_iterations"),
+        }
+    }
+
+    /// A run with a hook slower than the timeout times out, while a quick
+    /// one comes back.
+    #[test]
+    fn timeout_slow_hook() {
+        let slow = with_timeout(Duration::from_millis(50), || {
+            let runner: Runner<Lambda, LambdaAnalysis> = Runner::default()
+                .with_expr(&"(if true 1 2)".parse().unwrap())
+                .with_hook(|_| {
+                    thread::sleep(Duration::from_secs(1));
+                    Ok(())
+                })
+                .run(&rules());
+            runner.iterations.len()
+        });
+        assert_eq!(slow, None);
+        assert_eq!(with_timeout(Duration::from_secs(60), || 1), Some(1));
+    }
+
+    /// A panic in the timed function isn't mistaken for a timeout.
+    #[test]
+    #[should_panic(expected = "hook failed")]
+    fn timeout_resumes_panics() {
+        with_timeout(Duration::from_secs(60), || -> usize {
+            panic!("hook failed")
+        });
+    }
+
+    /// The root e-class of `lambda_bench2` only gains e-nodes over the run.
+    #[test]
+    fn root_nodes_non_decreasing() {
+        let report = lambda_bench2().run(&RunConfig::default()).unwrap();
+        assert!(report.root_nodes.len() > 1);
+        assert!(
+            report.root_nodes.windows(2).all(|w| w[0] <= w[1]),
+            "{:?}",
+            report.root_nodes
+        );
+    }
+
+    /// Tracking matches records one count per rule for each iteration run.
+    #[test]
+    fn rule_matches_per_iteration() {
+        let config = RunConfig {
+            track_matches: true,
+            ..Default::default()
+        };
+        let bench = lambda_bench2();
+        let report = bench.run(&config).unwrap();
+        assert_eq!(report.rule_matches.len(), bench.rules.len());
+        for (name, counts) in &report.rule_matches {
+            assert_eq!(counts.len(), report.iterations, "{}", name);
+        }
+    }
+
+    /// Only rules with some iteration's count strictly over the limit are
+    /// named.
+    #[test]
+    fn over_match_limit() {
+        let mut matches = BTreeMap::new();
+        matches.insert("a".to_string(), vec![0, 5, 2]);
+        matches.insert("b".to_string(), vec![3, 3]);
+        matches.insert("c".to_string(), vec![]);
+        matches.insert("d".to_string(), vec![4]);
+        assert_eq!(rules_over_match_limit(&matches, 3), vec!["a", "d"]);
+    }
+
+    /// Saturating `lambda_bench0` with the arithmetic rules, then with all of
+    /// them, ends where one run with all the rules does, since the last stage
+    /// has every rule.
+    #[test]
+    fn staged_run_matches_combined() {
+        let config = RunConfig::default();
+        let bench = lambda_bench0();
+        let combined = bench.run(&config).unwrap();
+        let stages = vec![rules_for(&[RuleTag::Arith]), rules()];
+        let (mut egraph, reports) = bench.staged_run(&config, stages).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].nodes_before, reports[0].nodes_after);
+        let root = egraph.add_expr(&bench.start_exprs[0].parse().unwrap());
+        let (_, best) = Extractor::new(&egraph, AstSize).find_best(root);
+        assert_eq!(combined.results, [best.pretty(80)]);
+    }
+
+    /// `lambda_bench2` is written with `fix`, and no rule makes a `letrec`, so
+    /// of these patterns only the `letrec` one is reported as matching nothing.
+    #[test]
+    fn zero_match_pats() {
+        let bench = lambda_bench2()
+            .with_bench_pats(vec!["(app ?f ?x)".into(), "(letrec ?v ?e ?body)".into()]);
+        let report = bench.run(&RunConfig::default()).unwrap();
+        assert_eq!(report.zero_match_pats, ["(letrec ?v ?e ?body)"]);
+    }
+
+    /// The first union traced for `(app (lam x (var x)) 1)` is `beta`'s, the
+    /// only rule with a match at the start.
+    #[test]
+    fn trace_starts_with_beta() {
+        let config = RunConfig {
+            trace: true,
+            ..Default::default()
+        };
+        let report = program_bench("beta", "(app (lam x (var x)) 1)")
+            .run(&config)
+            .unwrap();
+        let first = report.trace.first().expect("nothing traced");
+        assert_eq!((first.iteration, first.rule.as_str()), (0, "beta"));
+        assert_eq!(report.results, ["1"]);
+    }
+
+    /// A capacity hint doesn't change the e-graph a run ends with.
+    #[test]
+    fn initial_capacity_same_egraph() {
+        let run = |initial_capacity| {
+            let config = RunConfig {
+                initial_capacity,
+                ..Default::default()
+            };
+            let (runner, _) = lambda_bench0().saturate(&config).unwrap();
+            let egraph = &runner.egraph;
+            let size = (egraph.total_number_of_nodes(), egraph.number_of_classes());
+            (size, egraph_hash(egraph))
+        };
+        assert_eq!(run(None), run(Some(1000)));
+    }
+
+    /// Unions `?a` into the e-class of the match, then drops every e-node
+    /// there but the sums, leaving a sum of the e-class with itself.
+    struct Strand {
+        a: Var,
+    }
+
+    impl Applier<Lambda, LambdaAnalysis> for Strand {
+        fn apply_one(
+            &self,
+            egraph: &mut EGraph<Lambda, LambdaAnalysis>,
+            eclass: Id,
+            subst: &Subst,
+        ) -> Vec<Id> {
+            egraph.union(eclass, subst[self.a]);
+            egraph[eclass]
+                .nodes
+                .retain(|node| matches!(node, Lambda::Add(_)));
+            vec![eclass]
+        }
+    }
+
+    /// A root left with no finite term is skipped with a warning, and the
+    /// rest of the report still comes back.
+    #[test]
+    fn root_without_finite_term() {
+        let a = "?a".parse().unwrap();
+        let strand = egg::rewrite!("strand"; "(+ ?a 0)" => { Strand { a } });
+        let report = program_bench("strand", "(+ (var x) 0)")
+            .with_rules(vec![strand])
+            .run(&RunConfig::default())
+            .unwrap();
+        assert!(report.results.is_empty());
+        assert!(
+            report
+                .warnings
+                .iter()
+                .any(|w| w.contains("has no finite term to extract")),
+            "{:?}",
+            report.warnings
+        );
+    }
+
+    /// Two rule orders simplify `lambda_bench2` to the same result.
+    #[test]
+    fn shuffled_rules_same_result() {
+        let names = |seed| {
+            let mut names: Vec<String> = rules().iter().map(|r| r.name().to_string()).collect();
+            shuffle_rules(&mut names, seed);
+            names
+        };
+        assert_ne!(names(1), names(2));
+        let run = |seed| {
+            let config = RunConfig {
+                shuffle_rules_seed: Some(seed),
+                ..Default::default()
+            };
+            lambda_bench2().run(&config).unwrap().results
+        };
+        assert_eq!(run(1), run(2));
+    }
+}
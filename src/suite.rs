@@ -0,0 +1,221 @@
+use crate::lambda::{
+    alpha_hash, analysis_stats, parse_program, reset_analysis_stats, Lambda, LambdaAnalysis,
+};
+use crate::*;
+use std::collections::hash_map::{Entry, HashMap};
+
+/// A collection of benchmarks to run together.
+#[derive(Clone, Debug)]
+pub struct BenchSuite<L: Language, A: Analysis<L> + 'static> {
+    pub benches: Vec<Bench<L, A>>,
+}
+
+impl<L: Language, A: Analysis<L>> Default for BenchSuite<L, A> {
+    fn default() -> Self {
+        Self { benches: vec![] }
+    }
+}
+
+impl<L: Language, A: Analysis<L>> BenchSuite<L, A> {
+    pub fn new(benches: Vec<Bench<L, A>>) -> Self {
+        Self { benches }
+    }
+
+    /// Keeps only the first of the benches with the same `key`, returning the
+    /// names of the benches that were dropped alongside the one kept.
+    pub fn dedup_by_key<K: Eq + std::hash::Hash>(
+        &mut self,
+        mut key: impl FnMut(&Bench<L, A>) -> K,
+    ) -> Vec<(String, String)> {
+        let mut kept: HashMap<K, String> = HashMap::new();
+        let mut merged = vec![];
+        self.benches.retain(|bench| match kept.entry(key(bench)) {
+            Entry::Occupied(first) => {
+                merged.push((first.get().clone(), bench.name.clone()));
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(bench.name.clone());
+                true
+            }
+        });
+        merged
+    }
+}
+
+impl BenchSuite<Lambda, LambdaAnalysis> {
+    /// Drops benches with the same rules (by name) and the same programs up
+    /// to renaming bound variables, returning what it merged like
+    /// `dedup_by_key`. A program that doesn't parse is an `Error::Parse`,
+    /// and leaves the suite as it was.
+    pub fn dedup(&mut self) -> Result<Vec<(String, String)>, Error> {
+        let mut keys = vec![];
+        for bench in &self.benches {
+            let programs = bench
+                .start_exprs
+                .iter()
+                .map(|expr| parse_program(expr).map(|expr| alpha_hash(&expr)))
+                .collect::<Result<Vec<u64>, _>>()?;
+            let rules: Vec<String> = bench.rules.iter().map(|r| r.name().to_string()).collect();
+            keys.push((programs, rules));
+        }
+        // `dedup_by_key` takes each bench's key once, in order
+        let mut keys = keys.into_iter();
+        Ok(self.dedup_by_key(|_| keys.next().unwrap()))
+    }
+
+    /// `run_all_parallel`, also filling in `analysis_stats` for benches that
+    /// count analysis calls. The counters belong to the thread, and a thread
+    /// runs many benches, so each run resets them before it starts.
+    pub fn run_all_parallel_with_stats(
+        &self,
+        config: &RunConfig,
+    ) -> Result<Vec<BenchReport>, Error> {
+        use rayon::prelude::*;
+        self.benches
+            .par_iter()
+            .map(|bench| {
+                reset_analysis_stats();
+                let mut report = bench.run(config)?;
+                if bench.analysis.count_calls {
+                    report.analysis_stats = Some(analysis_stats());
+                }
+                Ok(report)
+            })
+            .collect()
+    }
+}
+
+impl<L, A> BenchSuite<L, A>
+where
+    L: Language + FromOp + std::fmt::Display + 'static,
+    A: Analysis<L> + Default + Clone,
+    A::Data: Clone,
+{
+    /// `Bench::dry_run` for every bench, stopping at the first error.
+    pub fn validate_all(&self, config: &RunConfig) -> Result<(), Error> {
+        self.benches
+            .iter()
+            .try_for_each(|bench| bench.dry_run(config))
+    }
+
+    /// Runs every bench in turn under `config`, stopping at the first error.
+    pub fn run_all(&self, config: &RunConfig) -> Result<Vec<BenchReport>, Error> {
+        self.benches.iter().map(|bench| bench.run(config)).collect()
+    }
+
+    /// Like `run_all`, but only runs the benches tagged `tag`.
+    pub fn run_tagged(&self, tag: &str, config: &RunConfig) -> Result<Vec<BenchReport>, Error> {
+        self.benches
+            .iter()
+            .filter(|bench| bench.tags.contains(&tag))
+            .map(|bench| bench.run(config))
+            .collect()
+    }
+
+    /// Like `run_all`, but runs the benches in parallel. Each builds its own
+    /// e-graph, and anything a run has to say ends up in its report, so runs
+    /// don't share any state, except for per-thread counters like lambda's
+    /// analysis stats (see `run_all_parallel_with_stats`). Reports come back
+    /// in the suite's order.
+    pub fn run_all_parallel(&self, config: &RunConfig) -> Result<Vec<BenchReport>, Error>
+    where
+        Bench<L, A>: Sync,
+    {
+        use rayon::prelude::*;
+        self.benches
+            .par_iter()
+            .map(|bench| bench.run(config))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambda::{
+        lambda_bench0, lambda_bench1, lambda_bench2, lambda_bench_onestep, program_bench,
+    };
+
+    /// Four benches run in parallel come back in order, with the results
+    /// and analysis call counts each gets when it runs alone.
+    #[test]
+    fn parallel_matches_serial() {
+        let benches: Vec<_> = vec![
+            lambda_bench0(),
+            lambda_bench1(),
+            lambda_bench2(),
+            lambda_bench_onestep(),
+        ]
+        .into_iter()
+        .map(|mut bench| {
+            bench.analysis.count_calls = true;
+            bench
+        })
+        .collect();
+        let suite = BenchSuite::new(benches);
+        let config = RunConfig::default();
+        let parallel = suite.run_all_parallel_with_stats(&config).unwrap();
+        assert_eq!(parallel.len(), 4);
+        for (bench, report) in suite.benches.iter().zip(&parallel) {
+            reset_analysis_stats();
+            let alone = bench.run(&config).unwrap();
+            let stats = analysis_stats();
+            let counted = report.analysis_stats.as_ref().unwrap();
+            assert_eq!(report.name, bench.name);
+            assert_eq!(report.results, alone.results);
+            assert_eq!(counted.make.calls, stats.make.calls, "{}", bench.name);
+            assert_eq!(counted.merge.calls, stats.merge.calls, "{}", bench.name);
+            assert_eq!(counted.modify.calls, stats.modify.calls, "{}", bench.name);
+        }
+    }
+
+    /// A bench with a malformed program fails `dry_run`, and so fails
+    /// `validate_all` for its suite, while a valid one passes both, all
+    /// without running anything.
+    #[test]
+    fn dry_run_catches_bad_program() {
+        let config = RunConfig::default();
+        let bad = program_bench("bad", "(+ 1");
+        assert!(matches!(bad.dry_run(&config), Err(Error::Parse(_))));
+        assert!(lambda_bench2().dry_run(&config).is_ok());
+        assert!(BenchSuite::new(vec![lambda_bench2()])
+            .validate_all(&config)
+            .is_ok());
+        let suite = BenchSuite::new(vec![lambda_bench2(), bad]);
+        assert!(matches!(suite.validate_all(&config), Err(Error::Parse(_))));
+    }
+
+    /// `run_tagged("recursion")` runs `lambda_bench2`, tagged with it, but not
+    /// `lambda_bench0`, which is only about arithmetic and control.
+    #[test]
+    fn run_tagged_recursion() {
+        assert!(lambda_bench2().tags.contains(&"recursion"));
+        assert!(!lambda_bench0().tags.contains(&"recursion"));
+        let suite = BenchSuite::new(vec![lambda_bench0(), lambda_bench2()]);
+        let reports = suite
+            .run_tagged("recursion", &RunConfig::default())
+            .unwrap();
+        let names: Vec<&str> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["lambda2"]);
+    }
+
+    /// `dedup` merges a bench into an earlier one with the same program up to
+    /// renaming, and fails on a program that doesn't parse without dropping
+    /// anything.
+    #[test]
+    fn dedup_renamed_program() {
+        let mut suite = BenchSuite::new(vec![
+            program_bench("id-x", "(lam x (var x))"),
+            program_bench("id-y", "(lam y (var y))"),
+            lambda_bench2(),
+        ]);
+        let merged = suite.dedup().unwrap();
+        assert_eq!(merged, [("id-x".to_string(), "id-y".to_string())]);
+        assert_eq!(suite.benches.len(), 2);
+
+        suite.benches.push(program_bench("bad", "(lam x"));
+        assert!(matches!(suite.dedup(), Err(Error::Parse(_))));
+        assert_eq!(suite.benches.len(), 3);
+    }
+}